@@ -8,42 +8,36 @@
 // If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
 //
 
-use async_trait::async_trait;
-use bitcoincore_rpc_async;
-use jsonrpc_async as jsonrpc;
-use serde;
-use serde_json;
+//! Wrapping a client so that calls are retried while the node is warming up
+//! or temporarily unreachable.
+use std::time::Duration;
 
-use bitcoincore_rpc_async::{Client, Error, Result, RpcApi};
+use bitcoincore_rpc_async::{Auth, Client, Error, RetryClient, RetryPolicy, RpcApi};
 
-pub struct RetryClient {
-    client: Client,
-}
+async fn main_result() -> Result<(), Error> {
+    let mut args = std::env::args();
+
+    let _exe_name = args.next().unwrap();
+
+    let url = args.next().expect("Usage: <rpc_url> <username> <password>");
+    let user = args.next().expect("no user given");
+    let pass = args.next().expect("no pass given");
 
-const INTERVAL: u64 = 1000;
-const RETRY_ATTEMPTS: u8 = 10;
-
-#[async_trait]
-impl RpcApi for RetryClient {
-    async fn call<T: for<'a> serde::de::Deserialize<'a>>(
-        &self,
-        cmd: &str,
-        args: &[serde_json::Value],
-    ) -> Result<T> {
-        for _ in 0..RETRY_ATTEMPTS {
-            match self.client.call(cmd, args).await {
-                Ok(ret) => return Ok(ret),
-                Err(Error::JsonRpc(jsonrpc::error::Error::Rpc(ref rpcerr)))
-                    if rpcerr.code == -28 =>
-                {
-                    ::std::thread::sleep(::std::time::Duration::from_millis(INTERVAL));
-                    continue;
-                }
-                Err(e) => return Err(e),
-            }
-        }
-        self.client.call(cmd, args).await
-    }
+    let policy = RetryPolicy {
+        max_attempts: 10,
+        initial_backoff: Duration::from_millis(500),
+        deadline: Some(Duration::from_secs(60)),
+        ..Default::default()
+    };
+    let rpc = RetryClient::new(Client::new(url, Auth::UserPass(user, pass)).await?, policy);
+
+    let bestblockcount = rpc.get_block_count().await?;
+    println!("best block height: {}", bestblockcount);
+
+    Ok(())
 }
 
-fn main() {}
+#[tokio::main]
+async fn main() {
+    main_result().await.unwrap();
+}
@@ -0,0 +1,293 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! JSON-RPC batch requests.
+
+use std::future::Future;
+use std::sync::Mutex;
+use std::task::Poll;
+use std::{mem, result};
+
+use futures::channel::oneshot;
+use futures::future::{self, BoxFuture, FutureExt};
+use jsonrpc_async as jsonrpc;
+use serde_json::value::RawValue;
+
+use async_trait::async_trait;
+use log::Level::Debug;
+use log::{debug, log_enabled};
+
+use crate::client::{log_response, Client, Result, RpcApi};
+use crate::error::Error;
+use crate::redact::redact_positional;
+use crate::rt;
+
+/// A call that is waiting for the next batch to be sent.
+struct QueuedCall {
+    cmd: String,
+    id: serde_json::Value,
+    args: Vec<Box<RawValue>>,
+    response: oneshot::Sender<Option<jsonrpc::Response>>,
+}
+
+/// Collects RPC calls and sends them together as JSON-RPC batch requests.
+///
+/// `Batch` implements [RpcApi], so any method of the trait can be called on
+/// it. Calls made on a `Batch` are queued instead of being sent immediately
+/// and only make progress while they are driven by [Batch::run]. Every call
+/// still gets its own result, so an error for one element of the batch does
+/// not affect the others.
+///
+/// ```no_run
+/// # async fn example(client: &bitcoincore_rpc_async::Client) -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::RpcApi;
+/// use futures::future::join_all;
+///
+/// let batch = client.batch();
+/// let hashes = batch.run(join_all((0..100).map(|h| batch.get_block_hash(h)))).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Batch<'a> {
+    client: &'a Client,
+    queue: Mutex<Vec<QueuedCall>>,
+}
+
+impl<'a> Batch<'a> {
+    pub(crate) fn new(client: &'a Client) -> Batch<'a> {
+        Batch {
+            client,
+            queue: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Drive `fut` to completion, sending the calls it makes on this batch.
+    ///
+    /// All calls that are queued while `fut` is waiting are sent in a single
+    /// JSON-RPC batch request. Methods that need the result of one call before
+    /// making the next one cause additional round-trips.
+    ///
+    /// Returns [Err] if a batch request as a whole failed, for example because
+    /// of a transport error. Errors for individual calls are returned to the
+    /// respective callers.
+    pub async fn run<F: Future>(&self, fut: F) -> Result<F::Output> {
+        let mut fut = Box::pin(fut);
+        let mut in_flight: Option<BoxFuture<'_, Result<()>>> = None;
+        future::poll_fn(move |cx| loop {
+            if let Some(ref mut send) = in_flight {
+                match send.as_mut().poll(cx) {
+                    Poll::Ready(Ok(())) => in_flight = None,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => {}
+                }
+            }
+            if let Poll::Ready(output) = fut.as_mut().poll(cx) {
+                return Poll::Ready(Ok(output));
+            }
+            if in_flight.is_some() {
+                return Poll::Pending;
+            }
+            let calls = mem::take(&mut *self.queue.lock().unwrap());
+            if calls.is_empty() {
+                return Poll::Pending;
+            }
+            in_flight = Some(self.send(calls).boxed());
+        })
+        .await
+    }
+
+    async fn send(&self, calls: Vec<QueuedCall>) -> Result<()> {
+        let client = self.client.get_jsonrpc_client();
+        let requests: Vec<_> = calls
+            .iter()
+            .map(|c| {
+                let mut request = client.build_request(&c.cmd, &c.args[..]);
+                request.id = c.id.clone();
+                request
+            })
+            .collect();
+        if log_enabled!(Debug) {
+            debug!(target: "bitcoincore_rpc", "JSON-RPC batch request with {} calls", requests.len());
+        }
+
+        // The batch gets the longest timeout of its calls.
+        let timeouts: Option<Vec<_>> = calls.iter().map(|c| self.client.timeout(&c.cmd)).collect();
+        let responses = match timeouts.and_then(|t| t.into_iter().max()) {
+            Some(timeout) => rt::timeout(timeout, client.send_batch(&requests))
+                .await
+                .map_err(|_| Error::Timeout)??,
+            None => client.send_batch(&requests).await?,
+        };
+        drop(requests);
+        for (call, response) in calls.into_iter().zip(responses) {
+            // The caller might have stopped waiting for the response.
+            let _ = call.response.send(response);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<'a> RpcApi for Batch<'a> {
+    /// Queue a `cmd` rpc with given `args` list for the next batch.
+    ///
+    /// The call is reported to the metrics of the client, and with the
+    /// `tracing` feature in a span, like calls that are not batched.
+    async fn call<T: for<'b> serde::de::Deserialize<'b>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        let raw_args = args
+            .iter()
+            .map(serde_json::value::to_raw_value)
+            .collect::<result::Result<_, serde_json::Error>>()?;
+        let id = self.client.get_jsonrpc_client().build_request(cmd, &[]).id;
+        let queued = async {
+            #[cfg(feature = "tracing")]
+            crate::spans::record_request(&id, cmd, args);
+            let (sender, receiver) = oneshot::channel();
+            self.queue.lock().unwrap().push(QueuedCall {
+                cmd: cmd.to_owned(),
+                id,
+                args: raw_args,
+                response: sender,
+            });
+            if log_enabled!(Debug) {
+                debug!(target: "bitcoincore_rpc", "JSON-RPC batched request: {} {}", cmd, redact_positional(cmd, args));
+            }
+
+            match receiver.await {
+                Ok(Some(resp)) => Ok(resp),
+                _ => Err(Error::MissingBatchResponse),
+            }
+        };
+        let resp = self.client.report(cmd, None, queued).await;
+        log_response(cmd, &resp);
+        Ok(resp?.result()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Answers `getblockcount` and fails every other method.
+    struct BatchTransport {
+        batches: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl jsonrpc::Transport for BatchTransport {
+        async fn send_request(
+            &self,
+            _: jsonrpc::Request<'_>,
+        ) -> result::Result<jsonrpc::Response, jsonrpc::Error> {
+            // Only batches are expected.
+            Err(jsonrpc::simple_http::Error::HttpParseError.into())
+        }
+
+        async fn send_batch(
+            &self,
+            reqs: &[jsonrpc::Request<'_>],
+        ) -> result::Result<Vec<jsonrpc::Response>, jsonrpc::Error> {
+            self.batches.fetch_add(1, Ordering::SeqCst);
+            Ok(reqs
+                .iter()
+                .map(|r| {
+                    let result = if r.method == "getblockcount" {
+                        Ok(42.into())
+                    } else {
+                        Err(jsonrpc::error::standard_error(
+                            jsonrpc::error::StandardError::MethodNotFound,
+                            None,
+                        ))
+                    };
+                    jsonrpc::error::result_to_response(result, r.id.clone())
+                })
+                .collect())
+        }
+
+        fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "batch")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let batches = Arc::new(AtomicUsize::new(0));
+        let client = Client::from_jsonrpc(jsonrpc::Client::with_transport(BatchTransport {
+            batches: batches.clone(),
+        }));
+
+        let batch = client.batch();
+        let (count, hash, count_again) = batch
+            .run(async {
+                futures::join!(
+                    batch.get_block_count(),
+                    batch.get_block_hash(1),
+                    batch.get_block_count()
+                )
+            })
+            .await
+            .unwrap();
+        assert_eq!(count.unwrap(), 42);
+        assert!(hash.is_err());
+        assert_eq!(count_again.unwrap(), 42);
+        assert_eq!(batches.load(Ordering::SeqCst), 1);
+    }
+
+    /// Answers batches of `getblockcount` calls over HTTP.
+    struct BatchHttp;
+
+    #[async_trait]
+    impl crate::Transport for BatchHttp {
+        async fn send(
+            &self,
+            _: &str,
+            _: &str,
+            _: Option<&str>,
+            body: &[u8],
+        ) -> Result<(u16, Vec<u8>)> {
+            let requests: Vec<serde_json::Value> = serde_json::from_slice(body)?;
+            let responses: Vec<_> = requests
+                .iter()
+                .map(|r| serde_json::json!({"result": 42, "error": null, "id": r["id"]}))
+                .collect();
+            Ok((200, serde_json::to_vec(&responses)?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_metrics() {
+        let metrics = Arc::new(crate::PrometheusMetrics::new());
+        let client = crate::ClientBuilder::new("http://localhost/".into(), crate::Auth::None)
+            .transport(Arc::new(BatchHttp))
+            .metrics(metrics.clone())
+            .build()
+            .await
+            .unwrap();
+
+        let batch = client.batch();
+        let (a, b) = batch
+            .run(async { futures::join!(batch.get_block_count(), batch.get_block_count()) })
+            .await
+            .unwrap();
+        assert_eq!((a.unwrap(), b.unwrap()), (42, 42));
+        let text = metrics.render();
+        assert!(text.contains("bitcoincore_rpc_calls_total{method=\"getblockcount\"} 2\n"));
+        assert!(
+            text.contains("bitcoincore_rpc_latency_seconds_count{method=\"getblockcount\"} 2\n")
+        );
+    }
+}
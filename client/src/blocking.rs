@@ -0,0 +1,821 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! A synchronous client, with the `blocking` feature.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+use bitcoin::secp256k1::ecdsa::Signature;
+use bitcoin::{
+    Address, Amount, Block, BlockHeader, OutPoint, PrivateKey, PublicKey, Script, Transaction,
+};
+use bitcoincore_rpc_json as json;
+use serde_json::value::RawValue;
+use tokio::runtime::{self, Runtime};
+
+use crate::bitcoin;
+use crate::builder::ClientBuilder;
+use crate::client::{Auth, Client, RawTx, Result, RpcApi};
+use crate::queryable;
+use crate::wallet::WalletClient;
+use crate::zmq::GetZmqNotificationsResult;
+
+/// A synchronous client that runs the calls of an async [RpcApi] on its own
+/// runtime, for programs that don't use async.
+///
+/// Every method of [RpcApi] has a blocking version with the same name and
+/// parameters. The methods must not be called from within an async runtime,
+/// use the async client there instead.
+///
+/// ```no_run
+/// # fn example() -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::{Auth, BlockingClient};
+///
+/// let client = BlockingClient::new("http://localhost:8332".into(), Auth::None)?;
+/// let count = client.get_block_count()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct BlockingClient<C = Client> {
+    client: C,
+    runtime: Arc<Runtime>,
+}
+
+impl BlockingClient<Client> {
+    /// Create a client to a bitcoind JSON-RPC server, like [Client::new].
+    pub fn new(url: String, auth: Auth) -> Result<BlockingClient<Client>> {
+        BlockingClient::from_builder(ClientBuilder::new(url, auth))
+    }
+
+    /// Create a client configured by `builder`.
+    pub fn from_builder(builder: ClientBuilder) -> Result<BlockingClient<Client>> {
+        let runtime = new_runtime()?;
+        let client = runtime.block_on(builder.build())?;
+        Ok(BlockingClient {
+            client,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Get a handle for the wallet `name`, like [Client::wallet], that runs
+    /// its calls on the runtime of this client.
+    pub fn wallet(&self, name: &str) -> BlockingClient<WalletClient<'_>> {
+        BlockingClient {
+            client: self.client.wallet(name),
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+impl<C: RpcApi + Sync> BlockingClient<C> {
+    /// Run the calls of `client` on a new runtime.
+    pub fn from_client(client: C) -> Result<BlockingClient<C>> {
+        Ok(BlockingClient {
+            client,
+            runtime: Arc::new(new_runtime()?),
+        })
+    }
+
+    /// Get the async client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+
+    /// Run `future` to completion on the runtime of this client, for
+    /// example to use a [crate::Batch].
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Blocking version of [RpcApi::call].
+    pub fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        self.block_on(self.client.call(cmd, args))
+    }
+
+    /// Blocking version of [RpcApi::call_raw].
+    pub fn call_raw(&self, cmd: &str, args: &[serde_json::Value]) -> Result<Box<RawValue>> {
+        self.block_on(self.client.call_raw(cmd, args))
+    }
+
+    /// Blocking version of [RpcApi::call_named].
+    pub fn call_named<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        self.block_on(self.client.call_named(cmd, args))
+    }
+
+    /// Blocking version of [RpcApi::get_by_id].
+    pub fn get_by_id<T: queryable::Queryable<C> + Sync + Send>(
+        &self,
+        id: &<T as queryable::Queryable<C>>::Id,
+    ) -> Result<T>
+    where
+        <T as queryable::Queryable<C>>::Id: Sync + Send,
+    {
+        self.block_on(self.client.get_by_id(id))
+    }
+
+    /// Blocking version of [RpcApi::get_network_info].
+    pub fn get_network_info(&self) -> Result<json::GetNetworkInfoResult> {
+        self.block_on(self.client.get_network_info())
+    }
+
+    /// Blocking version of [RpcApi::version].
+    pub fn version(&self) -> Result<usize> {
+        self.block_on(self.client.version())
+    }
+
+    /// Blocking version of [RpcApi::add_multisig_address].
+    pub fn add_multisig_address(
+        &self,
+        nrequired: usize,
+        keys: &[json::PubKeyOrAddress<'_>],
+        label: Option<&str>,
+        address_type: Option<json::AddressType>,
+    ) -> Result<json::AddMultiSigAddressResult> {
+        self.block_on(self.client.add_multisig_address(nrequired, keys, label, address_type))
+    }
+
+    /// Blocking version of [RpcApi::load_wallet].
+    pub fn load_wallet(&self, wallet: &str) -> Result<json::LoadWalletResult> {
+        self.block_on(self.client.load_wallet(wallet))
+    }
+
+    /// Blocking version of [RpcApi::unload_wallet].
+    pub fn unload_wallet(&self, wallet: Option<&str>) -> Result<()> {
+        self.block_on(self.client.unload_wallet(wallet))
+    }
+
+    /// Blocking version of [RpcApi::create_wallet].
+    pub fn create_wallet(
+        &self,
+        wallet: &str,
+        disable_private_keys: Option<bool>,
+        blank: Option<bool>,
+        passphrase: Option<&str>,
+        avoid_reuse: Option<bool>,
+    ) -> Result<json::LoadWalletResult> {
+        self.block_on(self.client.create_wallet(
+            wallet,
+            disable_private_keys,
+            blank,
+            passphrase,
+            avoid_reuse,
+        ))
+    }
+
+    /// Blocking version of [RpcApi::list_wallets].
+    pub fn list_wallets(&self) -> Result<Vec<String>> {
+        self.block_on(self.client.list_wallets())
+    }
+
+    /// Blocking version of [RpcApi::get_wallet_info].
+    pub fn get_wallet_info(&self) -> Result<json::GetWalletInfoResult> {
+        self.block_on(self.client.get_wallet_info())
+    }
+
+    /// Blocking version of [RpcApi::backup_wallet].
+    pub fn backup_wallet(&self, destination: Option<&str>) -> Result<()> {
+        self.block_on(self.client.backup_wallet(destination))
+    }
+
+    /// Blocking version of [RpcApi::dump_private_key].
+    pub fn dump_private_key(&self, address: &Address) -> Result<PrivateKey> {
+        self.block_on(self.client.dump_private_key(address))
+    }
+
+    /// Blocking version of [RpcApi::encrypt_wallet].
+    pub fn encrypt_wallet(&self, passphrase: &str) -> Result<()> {
+        self.block_on(self.client.encrypt_wallet(passphrase))
+    }
+
+    /// Blocking version of [RpcApi::get_difficulty].
+    pub fn get_difficulty(&self) -> Result<f64> {
+        self.block_on(self.client.get_difficulty())
+    }
+
+    /// Blocking version of [RpcApi::get_connection_count].
+    pub fn get_connection_count(&self) -> Result<usize> {
+        self.block_on(self.client.get_connection_count())
+    }
+
+    /// Blocking version of [RpcApi::get_block].
+    pub fn get_block(&self, hash: &bitcoin::BlockHash) -> Result<Block> {
+        self.block_on(self.client.get_block(hash))
+    }
+
+    /// Blocking version of [RpcApi::get_block_hex].
+    pub fn get_block_hex(&self, hash: &bitcoin::BlockHash) -> Result<String> {
+        self.block_on(self.client.get_block_hex(hash))
+    }
+
+    /// Blocking version of [RpcApi::get_block_info].
+    pub fn get_block_info(&self, hash: &bitcoin::BlockHash) -> Result<json::GetBlockResult> {
+        self.block_on(self.client.get_block_info(hash))
+    }
+
+    /// Blocking version of [RpcApi::get_block_header].
+    pub fn get_block_header(&self, hash: &bitcoin::BlockHash) -> Result<BlockHeader> {
+        self.block_on(self.client.get_block_header(hash))
+    }
+
+    /// Blocking version of [RpcApi::get_block_header_info].
+    pub fn get_block_header_info(
+        &self,
+        hash: &bitcoin::BlockHash,
+    ) -> Result<json::GetBlockHeaderResult> {
+        self.block_on(self.client.get_block_header_info(hash))
+    }
+
+    /// Blocking version of [RpcApi::get_mining_info].
+    pub fn get_mining_info(&self) -> Result<json::GetMiningInfoResult> {
+        self.block_on(self.client.get_mining_info())
+    }
+
+    /// Blocking version of [RpcApi::get_blockchain_info].
+    pub fn get_blockchain_info(&self) -> Result<json::GetBlockchainInfoResult> {
+        self.block_on(self.client.get_blockchain_info())
+    }
+
+    /// Blocking version of [RpcApi::get_block_count].
+    pub fn get_block_count(&self) -> Result<u64> {
+        self.block_on(self.client.get_block_count())
+    }
+
+    /// Blocking version of [RpcApi::get_best_block_hash].
+    pub fn get_best_block_hash(&self) -> Result<bitcoin::BlockHash> {
+        self.block_on(self.client.get_best_block_hash())
+    }
+
+    /// Blocking version of [RpcApi::get_block_hash].
+    pub fn get_block_hash(&self, height: u64) -> Result<bitcoin::BlockHash> {
+        self.block_on(self.client.get_block_hash(height))
+    }
+
+    /// Blocking version of [RpcApi::get_raw_transaction].
+    pub fn get_raw_transaction(
+        &self,
+        txid: &bitcoin::Txid,
+        block_hash: Option<&bitcoin::BlockHash>,
+    ) -> Result<Transaction> {
+        self.block_on(self.client.get_raw_transaction(txid, block_hash))
+    }
+
+    /// Blocking version of [RpcApi::get_raw_transaction_hex].
+    pub fn get_raw_transaction_hex(
+        &self,
+        txid: &bitcoin::Txid,
+        block_hash: Option<&bitcoin::BlockHash>,
+    ) -> Result<String> {
+        self.block_on(self.client.get_raw_transaction_hex(txid, block_hash))
+    }
+
+    /// Blocking version of [RpcApi::get_raw_transaction_info].
+    pub fn get_raw_transaction_info(
+        &self,
+        txid: &bitcoin::Txid,
+        block_hash: Option<&bitcoin::BlockHash>,
+    ) -> Result<json::GetRawTransactionResult> {
+        self.block_on(self.client.get_raw_transaction_info(txid, block_hash))
+    }
+
+    /// Blocking version of [RpcApi::get_block_filter].
+    pub fn get_block_filter(
+        &self,
+        block_hash: &bitcoin::BlockHash,
+    ) -> Result<json::GetBlockFilterResult> {
+        self.block_on(self.client.get_block_filter(block_hash))
+    }
+
+    /// Blocking version of [RpcApi::get_balance].
+    pub fn get_balance(
+        &self,
+        minconf: Option<usize>,
+        include_watchonly: Option<bool>,
+    ) -> Result<Amount> {
+        self.block_on(self.client.get_balance(minconf, include_watchonly))
+    }
+
+    /// Blocking version of [RpcApi::get_balances].
+    pub fn get_balances(&self) -> Result<json::GetBalancesResult> {
+        self.block_on(self.client.get_balances())
+    }
+
+    /// Blocking version of [RpcApi::get_received_by_address].
+    pub fn get_received_by_address(
+        &self,
+        address: &Address,
+        minconf: Option<u32>,
+    ) -> Result<Amount> {
+        self.block_on(self.client.get_received_by_address(address, minconf))
+    }
+
+    /// Blocking version of [RpcApi::get_transaction].
+    pub fn get_transaction(
+        &self,
+        txid: &bitcoin::Txid,
+        include_watchonly: Option<bool>,
+    ) -> Result<json::GetTransactionResult> {
+        self.block_on(self.client.get_transaction(txid, include_watchonly))
+    }
+
+    /// Blocking version of [RpcApi::list_transactions].
+    pub fn list_transactions(
+        &self,
+        label: Option<&str>,
+        count: Option<usize>,
+        skip: Option<usize>,
+        include_watchonly: Option<bool>,
+    ) -> Result<Vec<json::ListTransactionResult>> {
+        self.block_on(self.client.list_transactions(label, count, skip, include_watchonly))
+    }
+
+    /// Blocking version of [RpcApi::list_since_block].
+    pub fn list_since_block(
+        &self,
+        blockhash: Option<&bitcoin::BlockHash>,
+        target_confirmations: Option<usize>,
+        include_watchonly: Option<bool>,
+        include_removed: Option<bool>,
+    ) -> Result<json::ListSinceBlockResult> {
+        self.block_on(self.client.list_since_block(
+            blockhash,
+            target_confirmations,
+            include_watchonly,
+            include_removed,
+        ))
+    }
+
+    /// Blocking version of [RpcApi::get_tx_out].
+    pub fn get_tx_out(
+        &self,
+        txid: &bitcoin::Txid,
+        vout: u32,
+        include_mempool: Option<bool>,
+    ) -> Result<Option<json::GetTxOutResult>> {
+        self.block_on(self.client.get_tx_out(txid, vout, include_mempool))
+    }
+
+    /// Blocking version of [RpcApi::get_tx_out_proof].
+    pub fn get_tx_out_proof(
+        &self,
+        txids: &[bitcoin::Txid],
+        block_hash: Option<&bitcoin::BlockHash>,
+    ) -> Result<Vec<u8>> {
+        self.block_on(self.client.get_tx_out_proof(txids, block_hash))
+    }
+
+    /// Blocking version of [RpcApi::import_public_key].
+    pub fn import_public_key(
+        &self,
+        pubkey: &PublicKey,
+        label: Option<&str>,
+        rescan: Option<bool>,
+    ) -> Result<()> {
+        self.block_on(self.client.import_public_key(pubkey, label, rescan))
+    }
+
+    /// Blocking version of [RpcApi::import_private_key].
+    pub fn import_private_key(
+        &self,
+        privkey: &PrivateKey,
+        label: Option<&str>,
+        rescan: Option<bool>,
+    ) -> Result<()> {
+        self.block_on(self.client.import_private_key(privkey, label, rescan))
+    }
+
+    /// Blocking version of [RpcApi::import_address].
+    pub fn import_address(
+        &self,
+        address: &Address,
+        label: Option<&str>,
+        rescan: Option<bool>,
+    ) -> Result<()> {
+        self.block_on(self.client.import_address(address, label, rescan))
+    }
+
+    /// Blocking version of [RpcApi::import_address_script].
+    pub fn import_address_script(
+        &self,
+        script: &Script,
+        label: Option<&str>,
+        rescan: Option<bool>,
+        p2sh: Option<bool>,
+    ) -> Result<()> {
+        self.block_on(self.client.import_address_script(script, label, rescan, p2sh))
+    }
+
+    /// Blocking version of [RpcApi::import_multi].
+    pub fn import_multi(
+        &self,
+        requests: &[json::ImportMultiRequest<'_>],
+        options: Option<&json::ImportMultiOptions>,
+    ) -> Result<Vec<json::ImportMultiResult>> {
+        self.block_on(self.client.import_multi(requests, options))
+    }
+
+    /// Blocking version of [RpcApi::set_label].
+    pub fn set_label(&self, address: &Address, label: &str) -> Result<()> {
+        self.block_on(self.client.set_label(address, label))
+    }
+
+    /// Blocking version of [RpcApi::key_pool_refill].
+    pub fn key_pool_refill(&self, new_size: Option<usize>) -> Result<()> {
+        self.block_on(self.client.key_pool_refill(new_size))
+    }
+
+    /// Blocking version of [RpcApi::list_unspent].
+    pub fn list_unspent(
+        &self,
+        minconf: Option<usize>,
+        maxconf: Option<usize>,
+        addresses: Option<&[&Address]>,
+        include_unsafe: Option<bool>,
+        query_options: Option<json::ListUnspentQueryOptions>,
+    ) -> Result<Vec<json::ListUnspentResultEntry>> {
+        self.block_on(self.client.list_unspent(
+            minconf,
+            maxconf,
+            addresses,
+            include_unsafe,
+            query_options,
+        ))
+    }
+
+    /// Blocking version of [RpcApi::lock_unspent].
+    pub fn lock_unspent(&self, outputs: &[OutPoint]) -> Result<bool> {
+        self.block_on(self.client.lock_unspent(outputs))
+    }
+
+    /// Blocking version of [RpcApi::unlock_unspent].
+    pub fn unlock_unspent(&self, outputs: &[OutPoint]) -> Result<bool> {
+        self.block_on(self.client.unlock_unspent(outputs))
+    }
+
+    /// Blocking version of [RpcApi::list_received_by_address].
+    pub fn list_received_by_address(
+        &self,
+        address_filter: Option<&Address>,
+        minconf: Option<u32>,
+        include_empty: Option<bool>,
+        include_watchonly: Option<bool>,
+    ) -> Result<Vec<json::ListReceivedByAddressResult>> {
+        self.block_on(self.client.list_received_by_address(
+            address_filter,
+            minconf,
+            include_empty,
+            include_watchonly,
+        ))
+    }
+
+    /// Blocking version of [RpcApi::create_raw_transaction_hex].
+    pub fn create_raw_transaction_hex(
+        &self,
+        utxos: &[json::CreateRawTransactionInput],
+        outs: &HashMap<String, Amount>,
+        locktime: Option<i64>,
+        replaceable: Option<bool>,
+    ) -> Result<String> {
+        self.block_on(self.client.create_raw_transaction_hex(utxos, outs, locktime, replaceable))
+    }
+
+    /// Blocking version of [RpcApi::create_raw_transaction].
+    pub fn create_raw_transaction(
+        &self,
+        utxos: &[json::CreateRawTransactionInput],
+        outs: &HashMap<String, Amount>,
+        locktime: Option<i64>,
+        replaceable: Option<bool>,
+    ) -> Result<Transaction> {
+        self.block_on(self.client.create_raw_transaction(utxos, outs, locktime, replaceable))
+    }
+
+    /// Blocking version of [RpcApi::fund_raw_transaction].
+    pub fn fund_raw_transaction<R: RawTx + Sync + Send>(
+        &self,
+        tx: R,
+        options: Option<&json::FundRawTransactionOptions>,
+        is_witness: Option<bool>,
+    ) -> Result<json::FundRawTransactionResult> {
+        self.block_on(self.client.fund_raw_transaction(tx, options, is_witness))
+    }
+
+    /// Blocking version of [RpcApi::sign_raw_transaction].
+    #[deprecated]
+    #[allow(deprecated)]
+    pub fn sign_raw_transaction<R: RawTx + Sync + Send>(
+        &self,
+        tx: R,
+        utxos: Option<&[json::SignRawTransactionInput]>,
+        private_keys: Option<&[PrivateKey]>,
+        sighash_type: Option<json::SigHashType>,
+    ) -> Result<json::SignRawTransactionResult> {
+        self.block_on(self.client.sign_raw_transaction(tx, utxos, private_keys, sighash_type))
+    }
+
+    /// Blocking version of [RpcApi::sign_raw_transaction_with_wallet].
+    pub fn sign_raw_transaction_with_wallet<R: RawTx + Sync + Send>(
+        &self,
+        tx: R,
+        utxos: Option<&[json::SignRawTransactionInput]>,
+        sighash_type: Option<json::SigHashType>,
+    ) -> Result<json::SignRawTransactionResult> {
+        self.block_on(self.client.sign_raw_transaction_with_wallet(tx, utxos, sighash_type))
+    }
+
+    /// Blocking version of [RpcApi::sign_raw_transaction_with_key].
+    pub fn sign_raw_transaction_with_key<R: RawTx + Sync + Send>(
+        &self,
+        tx: R,
+        privkeys: &[PrivateKey],
+        prevtxs: Option<&[json::SignRawTransactionInput]>,
+        sighash_type: Option<json::SigHashType>,
+    ) -> Result<json::SignRawTransactionResult> {
+        self.block_on(self.client.sign_raw_transaction_with_key(
+            tx,
+            privkeys,
+            prevtxs,
+            sighash_type,
+        ))
+    }
+
+    /// Blocking version of [RpcApi::test_mempool_accept].
+    pub fn test_mempool_accept<R: RawTx + Sync + Send>(
+        &self,
+        rawtxs: &[R],
+    ) -> Result<Vec<json::TestMempoolAcceptResult>> {
+        self.block_on(self.client.test_mempool_accept(rawtxs))
+    }
+
+    /// Blocking version of [RpcApi::stop].
+    pub fn stop(&self) -> Result<String> {
+        self.block_on(self.client.stop())
+    }
+
+    /// Blocking version of [RpcApi::verify_message].
+    pub fn verify_message(
+        &self,
+        address: &Address,
+        signature: &Signature,
+        message: &str,
+    ) -> Result<bool> {
+        self.block_on(self.client.verify_message(address, signature, message))
+    }
+
+    /// Blocking version of [RpcApi::get_new_address].
+    pub fn get_new_address(
+        &self,
+        label: Option<&str>,
+        address_type: Option<json::AddressType>,
+    ) -> Result<Address> {
+        self.block_on(self.client.get_new_address(label, address_type))
+    }
+
+    /// Blocking version of [RpcApi::get_address_info].
+    pub fn get_address_info(&self, address: &Address) -> Result<json::GetAddressInfoResult> {
+        self.block_on(self.client.get_address_info(address))
+    }
+
+    /// Blocking version of [RpcApi::generate_to_address].
+    pub fn generate_to_address(
+        &self,
+        block_num: u64,
+        address: &Address,
+    ) -> Result<Vec<bitcoin::BlockHash>> {
+        self.block_on(self.client.generate_to_address(block_num, address))
+    }
+
+    /// Blocking version of [RpcApi::generate].
+    pub fn generate(
+        &self,
+        block_num: u64,
+        maxtries: Option<u64>,
+    ) -> Result<Vec<bitcoin::BlockHash>> {
+        self.block_on(self.client.generate(block_num, maxtries))
+    }
+
+    /// Blocking version of [RpcApi::invalidate_block].
+    pub fn invalidate_block(&self, block_hash: &bitcoin::BlockHash) -> Result<()> {
+        self.block_on(self.client.invalidate_block(block_hash))
+    }
+
+    /// Blocking version of [RpcApi::reconsider_block].
+    pub fn reconsider_block(&self, block_hash: &bitcoin::BlockHash) -> Result<()> {
+        self.block_on(self.client.reconsider_block(block_hash))
+    }
+
+    /// Blocking version of [RpcApi::get_raw_mempool].
+    pub fn get_raw_mempool(&self) -> Result<Vec<bitcoin::Txid>> {
+        self.block_on(self.client.get_raw_mempool())
+    }
+
+    /// Blocking version of [RpcApi::get_mempool_entry].
+    pub fn get_mempool_entry(&self, txid: &bitcoin::Txid) -> Result<json::GetMempoolEntryResult> {
+        self.block_on(self.client.get_mempool_entry(txid))
+    }
+
+    /// Blocking version of [RpcApi::send_to_address].
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_to_address(
+        &self,
+        address: &Address,
+        amount: Amount,
+        comment: Option<&str>,
+        comment_to: Option<&str>,
+        subtract_fee: Option<bool>,
+        replaceable: Option<bool>,
+        confirmation_target: Option<u32>,
+        estimate_mode: Option<json::EstimateMode>,
+    ) -> Result<bitcoin::Txid> {
+        self.block_on(self.client.send_to_address(
+            address,
+            amount,
+            comment,
+            comment_to,
+            subtract_fee,
+            replaceable,
+            confirmation_target,
+            estimate_mode,
+        ))
+    }
+
+    /// Blocking version of [RpcApi::get_peer_info].
+    pub fn get_peer_info(&self) -> Result<Vec<json::GetPeerInfoResult>> {
+        self.block_on(self.client.get_peer_info())
+    }
+
+    /// Blocking version of [RpcApi::ping].
+    pub fn ping(&self) -> Result<()> {
+        self.block_on(self.client.ping())
+    }
+
+    /// Blocking version of [RpcApi::send_raw_transaction].
+    pub fn send_raw_transaction<R: RawTx + Sync + Send>(&self, tx: R) -> Result<bitcoin::Txid> {
+        self.block_on(self.client.send_raw_transaction(tx))
+    }
+
+    /// Blocking version of [RpcApi::estimate_smart_fee].
+    pub fn estimate_smart_fee(
+        &self,
+        conf_target: u16,
+        estimate_mode: Option<json::EstimateMode>,
+    ) -> Result<json::EstimateSmartFeeResult> {
+        self.block_on(self.client.estimate_smart_fee(conf_target, estimate_mode))
+    }
+
+    /// Blocking version of [RpcApi::wait_for_new_block].
+    pub fn wait_for_new_block(&self, timeout: u64) -> Result<json::BlockRef> {
+        self.block_on(self.client.wait_for_new_block(timeout))
+    }
+
+    /// Blocking version of [RpcApi::wait_for_block].
+    pub fn wait_for_block(
+        &self,
+        blockhash: &bitcoin::BlockHash,
+        timeout: u64,
+    ) -> Result<json::BlockRef> {
+        self.block_on(self.client.wait_for_block(blockhash, timeout))
+    }
+
+    /// Blocking version of [RpcApi::wallet_create_funded_psbt].
+    pub fn wallet_create_funded_psbt(
+        &self,
+        inputs: &[json::CreateRawTransactionInput],
+        outputs: &HashMap<String, Amount>,
+        locktime: Option<i64>,
+        options: Option<json::WalletCreateFundedPsbtOptions>,
+        bip32derivs: Option<bool>,
+    ) -> Result<json::WalletCreateFundedPsbtResult> {
+        self.block_on(self.client.wallet_create_funded_psbt(
+            inputs,
+            outputs,
+            locktime,
+            options,
+            bip32derivs,
+        ))
+    }
+
+    /// Blocking version of [RpcApi::get_descriptor_info].
+    pub fn get_descriptor_info(&self, desc: &str) -> Result<json::GetDescriptorInfoResult> {
+        self.block_on(self.client.get_descriptor_info(desc))
+    }
+
+    /// Blocking version of [RpcApi::combine_psbt].
+    pub fn combine_psbt(&self, psbts: &[String]) -> Result<String> {
+        self.block_on(self.client.combine_psbt(psbts))
+    }
+
+    /// Blocking version of [RpcApi::finalize_psbt].
+    pub fn finalize_psbt(
+        &self,
+        psbt: &str,
+        extract: Option<bool>,
+    ) -> Result<json::FinalizePsbtResult> {
+        self.block_on(self.client.finalize_psbt(psbt, extract))
+    }
+
+    /// Blocking version of [RpcApi::derive_addresses].
+    pub fn derive_addresses(
+        &self,
+        descriptor: &str,
+        range: Option<[u32; 2]>,
+    ) -> Result<Vec<Address>> {
+        self.block_on(self.client.derive_addresses(descriptor, range))
+    }
+
+    /// Blocking version of [RpcApi::rescan_blockchain].
+    pub fn rescan_blockchain(
+        &self,
+        start_from: Option<usize>,
+        stop_height: Option<usize>,
+    ) -> Result<(usize, Option<usize>)> {
+        self.block_on(self.client.rescan_blockchain(start_from, stop_height))
+    }
+
+    /// Blocking version of [RpcApi::get_tx_out_set_info].
+    pub fn get_tx_out_set_info(&self) -> Result<json::GetTxOutSetInfoResult> {
+        self.block_on(self.client.get_tx_out_set_info())
+    }
+
+    /// Blocking version of [RpcApi::get_net_totals].
+    pub fn get_net_totals(&self) -> Result<json::GetNetTotalsResult> {
+        self.block_on(self.client.get_net_totals())
+    }
+
+    /// Blocking version of [RpcApi::get_network_hash_ps].
+    pub fn get_network_hash_ps(&self, nblocks: Option<u64>, height: Option<u64>) -> Result<f64> {
+        self.block_on(self.client.get_network_hash_ps(nblocks, height))
+    }
+
+    /// Blocking version of [RpcApi::get_zmq_notifications].
+    pub fn get_zmq_notifications(&self) -> Result<Vec<GetZmqNotificationsResult>> {
+        self.block_on(self.client.get_zmq_notifications())
+    }
+
+    /// Blocking version of [RpcApi::uptime].
+    pub fn uptime(&self) -> Result<u64> {
+        self.block_on(self.client.uptime())
+    }
+
+    /// Blocking version of [RpcApi::scan_tx_out_set_blocking].
+    pub fn scan_tx_out_set_blocking(
+        &self,
+        descriptors: &[json::ScanTxOutRequest],
+    ) -> Result<json::ScanTxOutResult> {
+        self.block_on(self.client.scan_tx_out_set_blocking(descriptors))
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for BlockingClient<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BlockingClient({:?})", self.client)
+    }
+}
+
+/// Create a runtime on the current thread, with the IO and time drivers that
+/// the clients need.
+fn new_runtime() -> Result<Runtime> {
+    Ok(runtime::Builder::new_current_thread().enable_all().build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::serve;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_blocking_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        listener.set_nonblocking(true).unwrap();
+        let server = thread::spawn(move || {
+            new_runtime().unwrap().block_on(async {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                serve(listener, 1, |_| (200, r#"{"result":42,"error":null,"id":0}"#)).await
+            })
+        });
+
+        let client = BlockingClient::new(url, Auth::None).unwrap();
+        assert_eq!(client.get_block_count().unwrap(), 42);
+        server.join().unwrap();
+    }
+}
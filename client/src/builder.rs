@@ -0,0 +1,285 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Building a [Client] with custom settings.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bitcoin::Network;
+use crate::cassette::Cassette;
+use crate::client::{Auth, Client, Result, Timeouts};
+use crate::config::RpcConfig;
+use crate::error::Error;
+use crate::metrics::Metrics;
+#[cfg(feature = "tokio")]
+use crate::socks::Socks5Proxy;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConfig;
+use crate::transport::{Endpoint, HttpOptions, Transport};
+
+/// The default timeout for a call.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A builder for a [Client] with custom settings.
+///
+/// ```no_run
+/// # async fn example() -> bitcoincore_rpc_async::Result<()> {
+/// use std::time::Duration;
+/// use bitcoincore_rpc_async::{Auth, ClientBuilder};
+///
+/// let client = ClientBuilder::new("http://localhost:8332".into(), Auth::None)
+///     .timeout(Duration::from_secs(5))
+///     .method_timeout("scantxoutset", Duration::from_secs(600))
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ClientBuilder {
+    url: String,
+    auth: Auth,
+    timeout: Option<Duration>,
+    method_timeouts: HashMap<String, Duration>,
+    cassette: Option<PathBuf>,
+    named_params: bool,
+    metrics: Option<Arc<dyn Metrics>>,
+    transport: Option<Arc<dyn Transport>>,
+    max_connections: Option<usize>,
+    max_response_size: Option<usize>,
+    #[cfg(feature = "tokio")]
+    proxy: Option<Socks5Proxy>,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConfig>,
+}
+
+impl ClientBuilder {
+    /// Start building a client to a bitcoind JSON-RPC server.
+    pub fn new(url: String, auth: Auth) -> ClientBuilder {
+        ClientBuilder {
+            url,
+            auth,
+            timeout: Some(DEFAULT_TIMEOUT),
+            method_timeouts: HashMap::new(),
+            cassette: None,
+            named_params: false,
+            metrics: None,
+            transport: None,
+            max_connections: None,
+            max_response_size: None,
+            #[cfg(feature = "tokio")]
+            proxy: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Start building a client to the bitcoind of `network` configured by
+    /// the `bitcoin.conf` file at `path`.
+    ///
+    /// The URL and authentication are taken from the RPC settings in the file
+    /// the same way `bitcoin-cli` does. The cookie file is looked up in the
+    /// `datadir` set in the file, or the directory of the file if not set.
+    pub fn from_conf(path: &Path, network: Network) -> Result<ClientBuilder> {
+        let conf = RpcConfig::read(path, network)?;
+        let datadir = path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(ClientBuilder::new(conf.url(network), conf.auth(datadir, network)))
+    }
+
+    /// Start building a client to the bitcoind of `network` with the data
+    /// directory `datadir`.
+    ///
+    /// Reads `<datadir>/bitcoin.conf` like [ClientBuilder::from_conf] if it
+    /// exists and otherwise uses the default port of `network` and the cookie
+    /// file in `datadir`.
+    pub fn from_datadir(datadir: &Path, network: Network) -> Result<ClientBuilder> {
+        let path = datadir.join("bitcoin.conf");
+        let conf = if path.exists() {
+            RpcConfig::read(&path, network)?
+        } else {
+            RpcConfig::default()
+        };
+        Ok(ClientBuilder::new(conf.url(network), conf.auth(datadir, network)))
+    }
+
+    /// Set the timeout for calls, [DEFAULT_TIMEOUT] if not set.
+    ///
+    /// Calls that don't finish in time fail with [Error::Timeout].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Don't time out calls, unless a timeout is set for their method.
+    pub fn no_timeout(mut self) -> Self {
+        self.timeout = None;
+        self
+    }
+
+    /// Set the timeout for calls of the RPC method `method`, like
+    /// `"scantxoutset"` or `"waitfornewblock"`, overriding the default timeout.
+    pub fn method_timeout(mut self, method: &str, timeout: Duration) -> Self {
+        self.method_timeouts.insert(method.to_owned(), timeout);
+        self
+    }
+
+    /// Record all requests and their responses to a new cassette file at
+    /// `path`, to replay them later by passing a [crate::ReplayTransport] to
+    /// [ClientBuilder::transport].
+    pub fn record(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cassette = Some(path.into());
+        self
+    }
+
+    /// Send only the optional parameters that were specified in calls of
+    /// [crate::RpcApi] methods, by name, instead of filling in the defaults
+    /// of this library for the parameters before them.
+    ///
+    /// This leaves the defaults to the node, but needs a node that supports
+    /// named parameters (Bitcoin Core 0.14 or later).
+    pub fn named_params(mut self, named_params: bool) -> Self {
+        self.named_params = named_params;
+        self
+    }
+
+    /// Report the calls of the client and its [crate::WalletClient]s, and
+    /// the bytes they transfer, to `metrics`.
+    pub fn metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Keep at most `max` keep-alive connections to the server, which also
+    /// limits the calls in flight. Further calls wait for a free connection
+    /// in the order they were made. Defaults to
+    /// [crate::DEFAULT_MAX_CONNECTIONS], the default `-rpcworkqueue` of
+    /// bitcoind.
+    ///
+    /// Panics if `max` is zero. Only has an effect on the built-in
+    /// [crate::TcpTransport] of the `tokio` feature.
+    pub fn max_connections(mut self, max: usize) -> Self {
+        assert!(max > 0, "max_connections must be positive");
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Fail calls whose response is larger than `max` bytes with an I/O
+    /// error, before reading it, so a broken or hostile server can't make the
+    /// client allocate arbitrary amounts of memory. Defaults to
+    /// [crate::DEFAULT_MAX_RESPONSE_SIZE].
+    ///
+    /// Has no effect with a custom transport.
+    pub fn max_response_size(mut self, max: usize) -> Self {
+        self.max_response_size = Some(max);
+        self
+    }
+
+    /// Send the requests with `transport` instead of the built-in transport
+    /// to the host of the URL, see [Transport].
+    ///
+    /// The host and path of the URL are still used for the `Host` header and
+    /// the endpoints of the requests, but the host isn't resolved.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Connect to the server through the SOCKS5 `proxy`, which resolves the
+    /// host of the URL.
+    ///
+    /// Without a proxy, `.onion` hosts are reached through a local Tor
+    /// daemon at `127.0.0.1:9050`. Has no effect with a custom transport.
+    #[cfg(feature = "tokio")]
+    pub fn proxy(mut self, proxy: Socks5Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Use `tls` for `https` URLs instead of the default [TlsConfig], which
+    /// trusts the root certificates of the Mozilla root program.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Create the client.
+    ///
+    /// Can only return [Err] when using cookie authentication, for invalid
+    /// URLs, invalid TLS settings or if the cassette file can't be created.
+    /// With cookie authentication, the cookie file is read again whenever the
+    /// server rejects the credentials.
+    pub async fn build(self) -> Result<Client> {
+        let cookie_file = match self.auth {
+            Auth::CookieFile(ref path) => Some(path.clone()),
+            _ => None,
+        };
+        let options = HttpOptions {
+            user_pass: self.auth.get_user_pass()?,
+            cookie_file,
+            metrics: self.metrics.clone(),
+            transport: self.transport,
+            max_connections: self.max_connections,
+            max_response_size: self.max_response_size,
+            #[cfg(feature = "tokio")]
+            proxy: self.proxy,
+            #[cfg(feature = "tls")]
+            tls: match self.tls {
+                Some(ref tls) => Some(tls.connector()?),
+                None => None,
+            },
+        };
+        let endpoint =
+            Endpoint::new(&self.url, options).await.map_err(|e| Error::JsonRpc(e.into()))?;
+        let timeouts = Timeouts {
+            default: self.timeout,
+            methods: self.method_timeouts,
+        };
+        let cassette = match self.cassette {
+            Some(path) => Some(Arc::new(Cassette::create(&path)?)),
+            None => None,
+        };
+        Ok(Client::from_endpoint(endpoint, timeouts, cassette, self.named_params, self.metrics))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::RpcApi;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_method_timeout() {
+        // Accept connections, but never answer.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut socks = Vec::new();
+            loop {
+                socks.push(listener.accept().await.unwrap());
+            }
+        });
+
+        let client = ClientBuilder::new(url, Auth::None)
+            .method_timeout("getblockcount", Duration::from_millis(50))
+            .build()
+            .await
+            .unwrap();
+        match client.get_block_count().await {
+            Err(Error::Timeout) => {}
+            r => panic!("expected timeout, got {:?}", r),
+        }
+        server.abort();
+    }
+}
@@ -0,0 +1,333 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Recording requests and responses to a cassette file and replaying them
+//! without a node.
+//!
+//! A cassette is a JSON lines file with one
+//! `{"path": .., "request": .., "response": ..}` object per request or batch
+//! request, where `path` is the URL path of the endpoint it was sent to, like
+//! `/` or `/wallet/<name>`. Sensitive parameters, like passphrases and
+//! private keys, are redacted in the recorded requests.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::{fmt, result};
+
+use async_trait::async_trait;
+use jsonrpc_async as jsonrpc;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::Value;
+
+use crate::redact;
+use crate::transport::Transport;
+
+/// One request or batch request and its response, a line of a cassette.
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    /// The URL path of the endpoint the request was sent to.
+    #[serde(default = "root_path")]
+    path: String,
+    request: Value,
+    response: Box<RawValue>,
+}
+
+/// The path of interactions recorded without one.
+fn root_path() -> String {
+    "/".to_owned()
+}
+
+/// A cassette file that interactions are appended to.
+#[derive(Debug)]
+pub(crate) struct Cassette {
+    file: Mutex<File>,
+}
+
+impl Cassette {
+    /// Create the cassette at `path`, replacing any existing file.
+    pub(crate) fn create(path: &Path) -> io::Result<Cassette> {
+        Ok(Cassette {
+            file: Mutex::new(File::create(path)?),
+        })
+    }
+
+    /// Append an interaction with the endpoint at the URL path `path` to the
+    /// cassette.
+    pub(crate) fn record<Req, Resp>(
+        &self,
+        path: &str,
+        request: &Req,
+        response: &Resp,
+    ) -> result::Result<(), jsonrpc::Error>
+    where
+        Req: Serialize + ?Sized,
+        Resp: Serialize + ?Sized,
+    {
+        let interaction = Interaction {
+            path: path.to_owned(),
+            request: redact_request(serde_json::to_value(request)?),
+            response: serde_json::value::to_raw_value(response)?,
+        };
+        let mut line = serde_json::to_vec(&interaction)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&line).map_err(|e| jsonrpc::Error::Transport(Box::new(e)))
+    }
+}
+
+/// A transport that records the requests sent through another transport and
+/// their responses to a cassette file.
+///
+/// Requests that fail in the inner transport are not recorded. Use
+/// [crate::ClientBuilder::record] to record the calls of a [crate::Client],
+/// including those of its [crate::WalletClient]s.
+pub struct RecordingTransport<T> {
+    inner: T,
+    cassette: Arc<Cassette>,
+    /// The URL path of the endpoint of `inner`.
+    path: String,
+}
+
+impl<T: jsonrpc::Transport> RecordingTransport<T> {
+    /// Record the requests sent through `inner` to a new cassette at `path`.
+    pub fn new(inner: T, path: &Path) -> io::Result<RecordingTransport<T>> {
+        Ok(RecordingTransport::with_cassette(inner, Arc::new(Cassette::create(path)?), "/"))
+    }
+
+    pub(crate) fn with_cassette(
+        inner: T,
+        cassette: Arc<Cassette>,
+        path: &str,
+    ) -> RecordingTransport<T> {
+        RecordingTransport {
+            inner,
+            cassette,
+            path: path.to_owned(),
+        }
+    }
+
+    /// Record the requests as sent to the endpoint at the URL path `path`,
+    /// like `/wallet/<name>`, instead of `/`.
+    pub fn endpoint(mut self, path: &str) -> RecordingTransport<T> {
+        self.path = path.to_owned();
+        self
+    }
+}
+
+#[async_trait]
+impl<T: jsonrpc::Transport> jsonrpc::Transport for RecordingTransport<T> {
+    async fn send_request(
+        &self,
+        req: jsonrpc::Request<'_>,
+    ) -> result::Result<jsonrpc::Response, jsonrpc::Error> {
+        let resp = self.inner.send_request(req.clone()).await?;
+        self.cassette.record(&self.path, &req, &resp)?;
+        Ok(resp)
+    }
+
+    async fn send_batch(
+        &self,
+        reqs: &[jsonrpc::Request<'_>],
+    ) -> result::Result<Vec<jsonrpc::Response>, jsonrpc::Error> {
+        let resps = self.inner.send_batch(reqs).await?;
+        self.cassette.record(&self.path, reqs, &resps)?;
+        Ok(resps)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt_target(f)
+    }
+}
+
+/// A [Transport] that answers requests with the responses recorded in a
+/// cassette file, without a node.
+///
+/// A request is answered with the response of the first recorded request
+/// to the same endpoint with the same method and parameters that wasn't
+/// replayed yet, so every recorded response is replayed once. Request ids
+/// and sensitive parameters, which are redacted in the cassette, are
+/// ignored. Requests without a recorded response fail with
+/// [crate::Error::Io].
+///
+/// The calls of [crate::WalletClient]s are replayed from the wallet
+/// endpoints they were recorded on, as long as the URL of the client has
+/// the same path as the one they were recorded with.
+///
+/// ```no_run
+/// # async fn example() -> bitcoincore_rpc_async::Result<()> {
+/// use std::sync::Arc;
+/// use bitcoincore_rpc_async::{Auth, ClientBuilder, ReplayTransport};
+///
+/// let transport = ReplayTransport::open("tests/data/getblockchaininfo.jsonl".as_ref())?;
+/// let client = ClientBuilder::new("http://localhost:8332".into(), Auth::None)
+///     .transport(Arc::new(transport))
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ReplayTransport {
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl ReplayTransport {
+    /// Load the cassette at `path`.
+    pub fn open(path: &Path) -> io::Result<ReplayTransport> {
+        let interactions = fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<result::Result<_, _>>()?;
+        Ok(ReplayTransport {
+            interactions: Mutex::new(interactions),
+        })
+    }
+
+    /// Take the recorded response for `request` to the endpoint at `path`,
+    /// with the ids of `request`.
+    fn replay(&self, path: &str, request: &Value) -> Option<Value> {
+        let key = without_ids(redact_request(request.clone()));
+        let mut interactions = self.interactions.lock().unwrap();
+        let index = interactions.iter().position(|i| {
+            i.path == path && without_ids(redact_request(i.request.clone())) == key
+        })?;
+        let mut response: Value = serde_json::from_str(interactions.remove(index).response.get())
+            .expect("a raw value is valid JSON");
+        match (request, &mut response) {
+            (Value::Object(request), Value::Object(response)) => {
+                response.insert("id".to_owned(), request.get("id").cloned().unwrap_or_default());
+            }
+            (Value::Array(requests), Value::Array(responses)) => {
+                for (request, response) in requests.iter().zip(responses) {
+                    if let Value::Object(ref mut response) = *response {
+                        response.insert("id".to_owned(), request["id"].clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+        Some(response)
+    }
+}
+
+/// Redact the sensitive parameters of a request or batch request.
+fn redact_request(mut request: Value) -> Value {
+    match request {
+        Value::Object(ref mut object) => {
+            let method = object.get("method").and_then(Value::as_str).unwrap_or("").to_owned();
+            if let Some(params) = object.get_mut("params") {
+                *params = redact::redact(&method, params);
+            }
+        }
+        Value::Array(ref mut requests) => {
+            for request in requests {
+                *request = redact_request(request.take());
+            }
+        }
+        _ => {}
+    }
+    request
+}
+
+/// Remove the ids from a request or batch request.
+fn without_ids(mut request: Value) -> Value {
+    match request {
+        Value::Object(ref mut object) => {
+            object.remove("id");
+        }
+        Value::Array(ref mut requests) => {
+            for request in requests {
+                if let Value::Object(ref mut object) = *request {
+                    object.remove("id");
+                }
+            }
+        }
+        _ => {}
+    }
+    request
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        _: Option<&str>,
+        body: &[u8],
+    ) -> crate::Result<(u16, Vec<u8>)> {
+        let request = match method {
+            "POST" => serde_json::from_slice(body)?,
+            _ => Value::Null,
+        };
+        match self.replay(path, &request) {
+            Some(response) => Ok((200, serde_json::to_vec(&response)?)),
+            None => {
+                let msg = format!("no recorded response for {} {} {}", method, path, request);
+                Err(io::Error::new(io::ErrorKind::NotFound, msg).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Auth, RpcApi};
+    use crate::test_server::serve;
+    use crate::ClientBuilder;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_record_replay() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Reply with the request path as result.
+        let server = tokio::spawn(serve(listener, 4, |req| {
+            (200, format!(r#"{{"result":"{}","error":null,"id":0}}"#, req.path))
+        }));
+
+        let path = std::env::temp_dir().join(format!("bitcoincore-rpc-cassette-{}", addr.port()));
+        let client = ClientBuilder::new(format!("http://{}", addr), Auth::None)
+            .record(&path)
+            .build()
+            .await
+            .unwrap();
+        let wallet = client.wallet("w");
+        assert_eq!(client.call::<String>("getbalance", &[]).await.unwrap(), "/");
+        assert_eq!(wallet.call::<String>("getbalance", &[]).await.unwrap(), "/wallet/w");
+        let args = serde_json::Map::new();
+        assert_eq!(wallet.call_named::<String>("getwalletinfo", &args).await.unwrap(), "/wallet/w");
+        let unlock = ["secret".into(), 60.into()];
+        wallet.call::<String>("walletpassphrase", &unlock).await.unwrap();
+        server.await.unwrap();
+        // Secrets are not recorded.
+        assert!(!fs::read_to_string(&path).unwrap().contains("secret"));
+
+        let replay = ReplayTransport::open(&path).unwrap();
+        let client = ClientBuilder::new("http://localhost:8332".into(), Auth::None)
+            .transport(Arc::new(replay))
+            .build()
+            .await
+            .unwrap();
+        let wallet = client.wallet("w");
+        // Calls on the wallet are told apart from the same calls on the node.
+        assert_eq!(wallet.call::<String>("getbalance", &[]).await.unwrap(), "/wallet/w");
+        assert_eq!(wallet.call_named::<String>("getwalletinfo", &args).await.unwrap(), "/wallet/w");
+        assert_eq!(client.call::<String>("getbalance", &[]).await.unwrap(), "/");
+        wallet.call::<String>("walletpassphrase", &unlock).await.unwrap();
+        // Every response is replayed once.
+        assert!(client.call::<String>("getbalance", &[]).await.is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -11,8 +11,11 @@
 use serde_json::value::RawValue;
 use std::collections::HashMap;
 use std::fs::File;
+use std::future::Future;
 use std::iter::FromIterator;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use std::{fmt, result};
 
 use super::bitcoin;
@@ -30,8 +33,20 @@ use bitcoin::{
 use log::Level::{Debug, Trace, Warn};
 use log::{debug, log_enabled, trace};
 
+use crate::batch::Batch;
+use crate::builder::ClientBuilder;
+use crate::cassette::{Cassette, RecordingTransport};
 use crate::error::*;
+use crate::metrics::{CallGuard, CallOutcome, Metrics};
 use crate::queryable;
+use crate::redact::{redact_named, redact_positional};
+use crate::rest::RestClient;
+use crate::rt;
+#[cfg(feature = "tracing")]
+use crate::spans;
+use crate::transport::{Endpoint, NamedRequest, PoolStats};
+use crate::wallet::WalletClient;
+use crate::zmq::GetZmqNotificationsResult;
 
 /// Crate-specific Result type, shorthand for `std::result::Result` with our
 /// crate-specific Error type;
@@ -143,6 +158,121 @@ fn handle_defaults<'a, 'b>(
     }
 }
 
+/// The parameter names of the methods that are called with optional
+/// parameters, for sending them by name.
+///
+/// Sorted by method, so it can be searched with [slice::binary_search_by_key].
+const PARAM_NAMES: &[(&str, &[&str])] = &[
+    ("addmultisigaddress", &["nrequired", "keys", "label", "address_type"]),
+    ("backupwallet", &["destination"]),
+    ("createrawtransaction", &["inputs", "outputs", "locktime", "replaceable"]),
+    (
+        "createwallet",
+        &["wallet_name", "disable_private_keys", "blank", "passphrase", "avoid_reuse"],
+    ),
+    ("deriveaddresses", &["descriptor", "range"]),
+    ("estimatesmartfee", &["conf_target", "estimate_mode"]),
+    ("finalizepsbt", &["psbt", "extract"]),
+    ("fundrawtransaction", &["hexstring", "options", "iswitness"]),
+    ("generate", &["nblocks", "maxtries"]),
+    ("getbalance", &["dummy", "minconf", "include_watchonly"]),
+    ("getnetworkhashps", &["nblocks", "height"]),
+    ("getnewaddress", &["label", "address_type"]),
+    ("getrawtransaction", &["txid", "verbose", "blockhash"]),
+    ("getreceivedbyaddress", &["address", "minconf"]),
+    ("gettransaction", &["txid", "include_watchonly"]),
+    ("gettxout", &["txid", "n", "include_mempool"]),
+    ("gettxoutproof", &["txids", "blockhash"]),
+    ("importaddress", &["address", "label", "rescan", "p2sh"]),
+    ("importmulti", &["requests", "options"]),
+    ("importprivkey", &["privkey", "label", "rescan"]),
+    ("importpubkey", &["pubkey", "label", "rescan"]),
+    ("keypoolrefill", &["newsize"]),
+    ("listreceivedbyaddress", &["minconf", "include_empty", "include_watchonly", "address_filter"]),
+    (
+        "listsinceblock",
+        &["blockhash", "target_confirmations", "include_watchonly", "include_removed"],
+    ),
+    ("listtransactions", &["label", "count", "skip", "include_watchonly"]),
+    ("listunspent", &["minconf", "maxconf", "addresses", "include_unsafe", "query_options"]),
+    ("rescanblockchain", &["start_height", "stop_height"]),
+    (
+        "sendtoaddress",
+        &[
+            "address",
+            "amount",
+            "comment",
+            "comment_to",
+            "subtractfeefromamount",
+            "replaceable",
+            "conf_target",
+            "estimate_mode",
+        ],
+    ),
+    ("signrawtransaction", &["hexstring", "prevtxs", "privkeys", "sighashtype"]),
+    ("signrawtransactionwithkey", &["hexstring", "privkeys", "prevtxs", "sighashtype"]),
+    ("signrawtransactionwithwallet", &["hexstring", "prevtxs", "sighashtype"]),
+    ("unloadwallet", &["wallet_name"]),
+    ("walletcreatefundedpsbt", &["inputs", "outputs", "locktime", "options", "bip32derivs"]),
+];
+
+/// The parameter names of `cmd`, if it is in [PARAM_NAMES].
+fn param_names(cmd: &str) -> Option<&'static [&'static str]> {
+    PARAM_NAMES.binary_search_by_key(&cmd, |&(c, _)| c).ok().map(|i| PARAM_NAMES[i].1)
+}
+
+/// Call `cmd` with the arguments in `args`, where optional arguments that
+/// weren't specified are `null`.
+///
+/// If `client` uses [RpcApi::named_params], only the specified arguments
+/// are sent, by name. Otherwise `args` is sent by position, with the gaps
+/// filled in from `defaults` like [handle_defaults] does.
+async fn call_optional<C, T>(
+    client: &C,
+    cmd: &str,
+    args: &mut [serde_json::Value],
+    defaults: &[serde_json::Value],
+) -> Result<T>
+where
+    C: RpcApi + Sync,
+    T: for<'a> serde::de::Deserialize<'a>,
+{
+    match param_names(cmd) {
+        Some(names) if client.named_params() => {
+            debug_assert_eq!(names.len(), args.len(), "parameter names of {}", cmd);
+            let named = names
+                .iter()
+                .zip(args.iter())
+                .filter(|&(_, arg)| *arg != serde_json::Value::Null)
+                .map(|(name, arg)| ((*name).to_owned(), arg.clone()))
+                .collect();
+            client.call_named(cmd, &named).await
+        }
+        _ => client.call(cmd, handle_defaults(args, defaults)).await,
+    }
+}
+
+/// The arguments of a call, by position or by name.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Params<'a> {
+    Positional(&'a [serde_json::Value]),
+    Named(&'a serde_json::Map<String, serde_json::Value>),
+}
+
+impl<'a> Params<'a> {
+    /// Call a `cmd` rpc with these arguments on `client`.
+    pub(crate) async fn call<C, T>(self, client: &C, cmd: &str) -> Result<T>
+    where
+        C: RpcApi + Sync,
+        T: for<'b> serde::de::Deserialize<'b>,
+    {
+        match self {
+            Params::Positional(args) => client.call(cmd, args).await,
+            Params::Named(args) => client.call_named(cmd, args).await,
+        }
+    }
+}
+
 /// Convert a possible-null result into an Option.
 fn opt_result<T: for<'a> serde::de::Deserialize<'a>>(
     result: serde_json::Value,
@@ -199,7 +329,7 @@ pub enum Auth {
 
 impl Auth {
     /// Convert into the arguments that jsonrpc::Client needs.
-    fn get_user_pass(self) -> Result<Option<(String, String)>> {
+    pub(crate) fn get_user_pass(self) -> Result<Option<(String, String)>> {
         use std::io::Read;
         match self {
             Auth::None => Ok(None),
@@ -226,6 +356,54 @@ pub trait RpcApi: Sized {
         args: &[serde_json::Value],
     ) -> Result<T>;
 
+    /// Call a `cmd` rpc with given `args` list, returning the result without
+    /// parsing it
+    ///
+    /// This allows parsing big results lazily, into types that borrow from
+    /// the result or not at all, for example to write them to a file.
+    /// [Client] returns the result as it was received, without copying it.
+    ///
+    /// ```no_run
+    /// # async fn example(client: &bitcoincore_rpc_async::Client) -> Result<(), Box<dyn std::error::Error>> {
+    /// use bitcoincore_rpc_async::RpcApi;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Block<'a> {
+    ///     #[serde(borrow)]
+    ///     tx: Vec<&'a serde_json::value::RawValue>,
+    /// }
+    ///
+    /// let hash = client.get_best_block_hash().await?;
+    /// let raw = client.call_raw("getblock", &[hash.to_string().into(), 2.into()]).await?;
+    /// let block: Block = serde_json::from_str(raw.get())?;
+    /// println!("{} transactions", block.tx.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn call_raw(&self, cmd: &str, args: &[serde_json::Value]) -> Result<Box<RawValue>> {
+        self.call(cmd, args).await
+    }
+
+    /// Call a `cmd` rpc with given named `args`
+    ///
+    /// Fails with [Error::NamedParamsUnsupported] unless implemented.
+    async fn call_named<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        let _ = (cmd, args);
+        Err(Error::NamedParamsUnsupported)
+    }
+
+    /// Whether the methods of this trait send only the optional parameters
+    /// that were specified, by name with [RpcApi::call_named], instead of
+    /// filling in defaults for the parameters before them.
+    fn named_params(&self) -> bool {
+        false
+    }
+
     /// Query an object implementing `Querable` type
     async fn get_by_id<T: queryable::Queryable<Self>>(
         &self,
@@ -264,7 +442,7 @@ pub trait RpcApi: Sized {
             opt_into_json(label)?,
             opt_into_json(address_type)?,
         ];
-        self.call("addmultisigaddress", handle_defaults(&mut args, &[into_json("")?, null()])).await
+        call_optional(self, "addmultisigaddress", &mut args, &[into_json("")?, null()]).await
     }
 
     async fn load_wallet(&self, wallet: &str) -> Result<json::LoadWalletResult> {
@@ -273,7 +451,7 @@ pub trait RpcApi: Sized {
 
     async fn unload_wallet(&self, wallet: Option<&str>) -> Result<()> {
         let mut args = [opt_into_json(wallet)?];
-        self.call("unloadwallet", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "unloadwallet", &mut args, &[null()]).await
     }
 
     async fn create_wallet(
@@ -291,9 +469,11 @@ pub trait RpcApi: Sized {
             opt_into_json(passphrase)?,
             opt_into_json(avoid_reuse)?,
         ];
-        self.call(
+        call_optional(
+            self,
             "createwallet",
-            handle_defaults(&mut args, &[false.into(), false.into(), into_json("")?, false.into()]),
+            &mut args,
+            &[false.into(), false.into(), into_json("")?, false.into()],
         )
         .await
     }
@@ -308,7 +488,7 @@ pub trait RpcApi: Sized {
 
     async fn backup_wallet(&self, destination: Option<&str>) -> Result<()> {
         let mut args = [opt_into_json(destination)?];
-        self.call("backupwallet", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "backupwallet", &mut args, &[null()]).await
     }
 
     async fn dump_private_key(&self, address: &Address) -> Result<PrivateKey> {
@@ -453,8 +633,7 @@ pub trait RpcApi: Sized {
         block_hash: Option<&bitcoin::BlockHash>,
     ) -> Result<Transaction> {
         let mut args = [into_json(txid)?, into_json(false)?, opt_into_json(block_hash)?];
-        let hex: String =
-            self.call("getrawtransaction", handle_defaults(&mut args, &[null()])).await?;
+        let hex: String = call_optional(self, "getrawtransaction", &mut args, &[null()]).await?;
         let bytes: Vec<u8> = FromHex::from_hex(&hex)?;
         Ok(bitcoin::consensus::encode::deserialize(&bytes)?)
     }
@@ -465,7 +644,7 @@ pub trait RpcApi: Sized {
         block_hash: Option<&bitcoin::BlockHash>,
     ) -> Result<String> {
         let mut args = [into_json(txid)?, into_json(false)?, opt_into_json(block_hash)?];
-        self.call("getrawtransaction", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "getrawtransaction", &mut args, &[null()]).await
     }
 
     async fn get_raw_transaction_info(
@@ -474,7 +653,7 @@ pub trait RpcApi: Sized {
         block_hash: Option<&bitcoin::BlockHash>,
     ) -> Result<json::GetRawTransactionResult> {
         let mut args = [into_json(txid)?, into_json(true)?, opt_into_json(block_hash)?];
-        self.call("getrawtransaction", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "getrawtransaction", &mut args, &[null()]).await
     }
 
     async fn get_block_filter(
@@ -491,7 +670,7 @@ pub trait RpcApi: Sized {
     ) -> Result<Amount> {
         let mut args = ["*".into(), opt_into_json(minconf)?, opt_into_json(include_watchonly)?];
         Ok(Amount::from_btc(
-            self.call("getbalance", handle_defaults(&mut args, &[0.into(), null()])).await?,
+            call_optional(self, "getbalance", &mut args, &[0.into(), null()]).await?,
         )?)
     }
 
@@ -506,7 +685,7 @@ pub trait RpcApi: Sized {
     ) -> Result<Amount> {
         let mut args = [address.to_string().into(), opt_into_json(minconf)?];
         Ok(Amount::from_btc(
-            self.call("getreceivedbyaddress", handle_defaults(&mut args, &[null()])).await?,
+            call_optional(self, "getreceivedbyaddress", &mut args, &[null()]).await?,
         )?)
     }
 
@@ -516,7 +695,7 @@ pub trait RpcApi: Sized {
         include_watchonly: Option<bool>,
     ) -> Result<json::GetTransactionResult> {
         let mut args = [into_json(txid)?, opt_into_json(include_watchonly)?];
-        self.call("gettransaction", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "gettransaction", &mut args, &[null()]).await
     }
 
     async fn list_transactions(
@@ -532,8 +711,7 @@ pub trait RpcApi: Sized {
             opt_into_json(skip)?,
             opt_into_json(include_watchonly)?,
         ];
-        self.call("listtransactions", handle_defaults(&mut args, &[10.into(), 0.into(), null()]))
-            .await
+        call_optional(self, "listtransactions", &mut args, &[10.into(), 0.into(), null()]).await
     }
 
     async fn list_since_block(
@@ -549,7 +727,7 @@ pub trait RpcApi: Sized {
             opt_into_json(include_watchonly)?,
             opt_into_json(include_removed)?,
         ];
-        self.call("listsinceblock", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "listsinceblock", &mut args, &[null()]).await
     }
 
     async fn get_tx_out(
@@ -559,7 +737,7 @@ pub trait RpcApi: Sized {
         include_mempool: Option<bool>,
     ) -> Result<Option<json::GetTxOutResult>> {
         let mut args = [into_json(txid)?, into_json(vout)?, opt_into_json(include_mempool)?];
-        opt_result(self.call("gettxout", handle_defaults(&mut args, &[null()])).await?)
+        opt_result(call_optional(self, "gettxout", &mut args, &[null()]).await?)
     }
 
     async fn get_tx_out_proof(
@@ -568,7 +746,7 @@ pub trait RpcApi: Sized {
         block_hash: Option<&bitcoin::BlockHash>,
     ) -> Result<Vec<u8>> {
         let mut args = [into_json(txids)?, opt_into_json(block_hash)?];
-        let hex: String = self.call("gettxoutproof", handle_defaults(&mut args, &[null()])).await?;
+        let hex: String = call_optional(self, "gettxoutproof", &mut args, &[null()]).await?;
         Ok(FromHex::from_hex(&hex)?)
     }
 
@@ -579,7 +757,7 @@ pub trait RpcApi: Sized {
         rescan: Option<bool>,
     ) -> Result<()> {
         let mut args = [pubkey.to_string().into(), opt_into_json(label)?, opt_into_json(rescan)?];
-        self.call("importpubkey", handle_defaults(&mut args, &[into_json("")?, null()])).await
+        call_optional(self, "importpubkey", &mut args, &[into_json("")?, null()]).await
     }
 
     async fn import_private_key(
@@ -589,7 +767,7 @@ pub trait RpcApi: Sized {
         rescan: Option<bool>,
     ) -> Result<()> {
         let mut args = [privkey.to_string().into(), opt_into_json(label)?, opt_into_json(rescan)?];
-        self.call("importprivkey", handle_defaults(&mut args, &[into_json("")?, null()])).await
+        call_optional(self, "importprivkey", &mut args, &[into_json("")?, null()]).await
     }
 
     async fn import_address(
@@ -598,8 +776,10 @@ pub trait RpcApi: Sized {
         label: Option<&str>,
         rescan: Option<bool>,
     ) -> Result<()> {
-        let mut args = [address.to_string().into(), opt_into_json(label)?, opt_into_json(rescan)?];
-        self.call("importaddress", handle_defaults(&mut args, &[into_json("")?, null()])).await
+        let mut args =
+            [address.to_string().into(), opt_into_json(label)?, opt_into_json(rescan)?, null()];
+        call_optional(self, "importaddress", &mut args, &[into_json("")?, true.into(), null()])
+            .await
     }
 
     async fn import_address_script(
@@ -615,11 +795,8 @@ pub trait RpcApi: Sized {
             opt_into_json(rescan)?,
             opt_into_json(p2sh)?,
         ];
-        self.call(
-            "importaddress",
-            handle_defaults(&mut args, &[into_json("")?, true.into(), null()]),
-        )
-        .await
+        call_optional(self, "importaddress", &mut args, &[into_json("")?, true.into(), null()])
+            .await
     }
 
     async fn import_multi(
@@ -632,7 +809,7 @@ pub trait RpcApi: Sized {
             json_requests.push(serde_json::to_value(req)?);
         }
         let mut args = [json_requests.into(), opt_into_json(options)?];
-        self.call("importmulti", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "importmulti", &mut args, &[null()]).await
     }
 
     async fn set_label(&self, address: &Address, label: &str) -> Result<()> {
@@ -641,7 +818,7 @@ pub trait RpcApi: Sized {
 
     async fn key_pool_refill(&self, new_size: Option<usize>) -> Result<()> {
         let mut args = [opt_into_json(new_size)?];
-        self.call("keypoolrefill", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "keypoolrefill", &mut args, &[null()]).await
     }
 
     async fn list_unspent(
@@ -660,7 +837,7 @@ pub trait RpcApi: Sized {
             opt_into_json(query_options)?,
         ];
         let defaults = [into_json(0)?, into_json(9999999)?, empty_arr(), into_json(true)?, null()];
-        self.call("listunspent", handle_defaults(&mut args, &defaults)).await
+        call_optional(self, "listunspent", &mut args, &defaults).await
     }
 
     /// To unlock, use [unlock_unspent].
@@ -694,7 +871,7 @@ pub trait RpcApi: Sized {
             opt_into_json(address_filter)?,
         ];
         let defaults = [1.into(), false.into(), false.into(), null()];
-        self.call("listreceivedbyaddress", handle_defaults(&mut args, &defaults)).await
+        call_optional(self, "listreceivedbyaddress", &mut args, &defaults).await
     }
 
     async fn create_raw_transaction_hex(
@@ -714,7 +891,7 @@ pub trait RpcApi: Sized {
             opt_into_json(replaceable)?,
         ];
         let defaults = [into_json(0i64)?, null()];
-        self.call("createrawtransaction", handle_defaults(&mut args, &defaults)).await
+        call_optional(self, "createrawtransaction", &mut args, &defaults).await
     }
 
     async fn create_raw_transaction(
@@ -741,7 +918,7 @@ pub trait RpcApi: Sized {
     {
         let mut args = [tx.raw_hex().into(), opt_into_json(options)?, opt_into_json(is_witness)?];
         let defaults = [empty_obj(), null()];
-        self.call("fundrawtransaction", handle_defaults(&mut args, &defaults)).await
+        call_optional(self, "fundrawtransaction", &mut args, &defaults).await
     }
 
     #[deprecated]
@@ -762,7 +939,7 @@ pub trait RpcApi: Sized {
             opt_into_json(sighash_type)?,
         ];
         let defaults = [empty_arr(), empty_arr(), null()];
-        self.call("signrawtransaction", handle_defaults(&mut args, &defaults)).await
+        call_optional(self, "signrawtransaction", &mut args, &defaults).await
     }
 
     async fn sign_raw_transaction_with_wallet<R: RawTx>(
@@ -776,7 +953,7 @@ pub trait RpcApi: Sized {
     {
         let mut args = [tx.raw_hex().into(), opt_into_json(utxos)?, opt_into_json(sighash_type)?];
         let defaults = [empty_arr(), null()];
-        self.call("signrawtransactionwithwallet", handle_defaults(&mut args, &defaults)).await
+        call_optional(self, "signrawtransactionwithwallet", &mut args, &defaults).await
     }
 
     async fn sign_raw_transaction_with_key<R: RawTx>(
@@ -796,7 +973,7 @@ pub trait RpcApi: Sized {
             opt_into_json(sighash_type)?,
         ];
         let defaults = [empty_arr(), null()];
-        self.call("signrawtransactionwithkey", handle_defaults(&mut args, &defaults)).await
+        call_optional(self, "signrawtransactionwithkey", &mut args, &defaults).await
     }
 
     async fn test_mempool_accept<R: RawTx>(
@@ -831,7 +1008,9 @@ pub trait RpcApi: Sized {
         label: Option<&str>,
         address_type: Option<json::AddressType>,
     ) -> Result<Address> {
-        self.call("getnewaddress", &[opt_into_json(label)?, opt_into_json(address_type)?]).await
+        let mut args = [opt_into_json(label)?, opt_into_json(address_type)?];
+        // Without defaults all arguments are sent by position, nulls included.
+        call_optional(self, "getnewaddress", &mut args, &[]).await
     }
 
     async fn get_address_info(&self, address: &Address) -> Result<json::GetAddressInfoResult> {
@@ -856,7 +1035,8 @@ pub trait RpcApi: Sized {
         block_num: u64,
         maxtries: Option<u64>,
     ) -> Result<Vec<bitcoin::BlockHash>> {
-        self.call("generate", &[block_num.into(), opt_into_json(maxtries)?]).await
+        let mut args = [block_num.into(), opt_into_json(maxtries)?];
+        call_optional(self, "generate", &mut args, &[]).await
     }
 
     /// Mark a block as invalid by `block_hash`
@@ -900,12 +1080,11 @@ pub trait RpcApi: Sized {
             opt_into_json(confirmation_target)?,
             opt_into_json(estimate_mode)?,
         ];
-        self.call(
+        call_optional(
+            self,
             "sendtoaddress",
-            handle_defaults(
-                &mut args,
-                &["".into(), "".into(), false.into(), false.into(), 6.into(), null()],
-            ),
+            &mut args,
+            &["".into(), "".into(), false.into(), false.into(), 6.into(), null()],
         )
         .await
     }
@@ -943,7 +1122,7 @@ pub trait RpcApi: Sized {
         estimate_mode: Option<json::EstimateMode>,
     ) -> Result<json::EstimateSmartFeeResult> {
         let mut args = [into_json(conf_target)?, opt_into_json(estimate_mode)?];
-        self.call("estimatesmartfee", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "estimatesmartfee", &mut args, &[null()]).await
     }
 
     /// Waits for a specific new block and returns useful info about it.
@@ -992,9 +1171,11 @@ pub trait RpcApi: Sized {
             opt_into_json(options)?,
             opt_into_json(bip32derivs)?,
         ];
-        self.call(
+        call_optional(
+            self,
             "walletcreatefundedpsbt",
-            handle_defaults(&mut args, &[0.into(), serde_json::Map::new().into(), false.into()]),
+            &mut args,
+            &[0.into(), serde_json::Map::new().into(), false.into()],
         )
         .await
     }
@@ -1013,7 +1194,7 @@ pub trait RpcApi: Sized {
         extract: Option<bool>,
     ) -> Result<json::FinalizePsbtResult> {
         let mut args = [into_json(psbt)?, opt_into_json(extract)?];
-        self.call("finalizepsbt", handle_defaults(&mut args, &[true.into()])).await
+        call_optional(self, "finalizepsbt", &mut args, &[true.into()]).await
     }
 
     async fn derive_addresses(
@@ -1022,7 +1203,7 @@ pub trait RpcApi: Sized {
         range: Option<[u32; 2]>,
     ) -> Result<Vec<Address>> {
         let mut args = [into_json(descriptor)?, opt_into_json(range)?];
-        self.call("deriveaddresses", handle_defaults(&mut args, &[null()])).await
+        call_optional(self, "deriveaddresses", &mut args, &[null()]).await
     }
 
     async fn rescan_blockchain(
@@ -1038,7 +1219,7 @@ pub trait RpcApi: Sized {
             pub stop_height: Option<usize>,
         }
         let res: Response =
-            self.call("rescanblockchain", handle_defaults(&mut args, &[0.into(), null()])).await?;
+            call_optional(self, "rescanblockchain", &mut args, &[0.into(), null()]).await?;
         Ok((res.start_height, res.stop_height))
     }
 
@@ -1057,7 +1238,14 @@ pub trait RpcApi: Sized {
     /// Returns the estimated network hashes per second based on the last n blocks.
     async fn get_network_hash_ps(&self, nblocks: Option<u64>, height: Option<u64>) -> Result<f64> {
         let mut args = [opt_into_json(nblocks)?, opt_into_json(height)?];
-        self.call("getnetworkhashps", handle_defaults(&mut args, &[null(), null()])).await
+        call_optional(self, "getnetworkhashps", &mut args, &[null(), null()]).await
+    }
+
+    /// Returns the enabled ZMQ notifications and their addresses.
+    ///
+    /// See [crate::ZmqSubscriber::from_notifications] to subscribe to them.
+    async fn get_zmq_notifications(&self) -> Result<Vec<GetZmqNotificationsResult>> {
+        self.call("getzmqnotifications", &[]).await
     }
 
     /// Returns the total uptime of the server in seconds
@@ -1073,9 +1261,31 @@ pub trait RpcApi: Sized {
     }
 }
 
+/// The timeouts for calls made by a [Client].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Timeouts {
+    /// The timeout for methods without a timeout in `methods`.
+    pub default: Option<Duration>,
+    pub methods: HashMap<String, Duration>,
+}
+
+impl Timeouts {
+    fn get(&self, cmd: &str) -> Option<Duration> {
+        self.methods.get(cmd).cloned().or(self.default)
+    }
+}
+
 /// Client implements a JSON-RPC client for the Bitcoin Core daemon or compatible APIs.
 pub struct Client {
     client: jsonrpc::client::Client,
+    /// The endpoint behind `client`, if created from a URL.
+    endpoint: Option<Endpoint>,
+    timeouts: Timeouts,
+    /// The cassette to record the requests to all endpoints to.
+    cassette: Option<Arc<Cassette>>,
+    /// Whether optional parameters are sent by name.
+    named_params: bool,
+    metrics: Option<Arc<dyn Metrics>>,
 }
 
 impl fmt::Debug for Client {
@@ -1087,25 +1297,45 @@ impl fmt::Debug for Client {
 impl Client {
     /// Creates a client to a bitcoind JSON-RPC server.
     ///
-    /// Can only return [Err] when using cookie authentication.
+    /// Can only return [Err] when using cookie authentication. `https://`
+    /// URLs need the `tls` feature. With the `tokio` feature, `.onion` hosts
+    /// are reached through a local Tor daemon at `127.0.0.1:9050`, see
+    /// [ClientBuilder::proxy].
+    ///
+    /// Use [ClientBuilder] to configure the client further.
     pub async fn new(url: String, auth: Auth) -> Result<Self> {
-        let mut client = jsonrpc::simple_http::SimpleHttpTransport::builder()
-            .url(&url)
-            .await
-            .map_err(|e| Error::JsonRpc(e.into()))?;
-        if let Some((user, pass)) = auth.get_user_pass()? {
-            client = client.auth(user, Some(pass));
-        }
+        ClientBuilder::new(url, auth).build().await
+    }
 
-        Ok(Client {
-            client: jsonrpc::client::Client::with_transport(client.build()),
-        })
+    pub(crate) fn from_endpoint(
+        endpoint: Endpoint,
+        timeouts: Timeouts,
+        cassette: Option<Arc<Cassette>>,
+        named_params: bool,
+        metrics: Option<Arc<dyn Metrics>>,
+    ) -> Client {
+        Client {
+            client: jsonrpc_client(endpoint.clone(), &cassette),
+            endpoint: Some(endpoint),
+            timeouts,
+            cassette,
+            named_params,
+            metrics,
+        }
     }
 
     /// Create a new Client.
+    ///
+    /// Wallet RPCs on handles returned by [Client::wallet] fail for clients
+    /// created this way, since there is no URL to derive wallet endpoints from.
     pub fn from_jsonrpc(client: jsonrpc::client::Client) -> Client {
         Client {
             client: client,
+            endpoint: None,
+            timeouts: Timeouts::default(),
+            cassette: None,
+            named_params: false,
+            metrics: None,
         }
     }
 
@@ -1113,6 +1343,166 @@ impl Client {
     pub fn get_jsonrpc_client(&self) -> &jsonrpc::client::Client {
         &self.client
     }
+
+    /// The timeout for calls of `cmd`, if there is one.
+    pub fn timeout(&self, cmd: &str) -> Option<Duration> {
+        self.timeouts.get(cmd)
+    }
+
+    /// Call a `cmd` rpc with given `args` list on `client`, which is either
+    /// the client of `self` or a client for the endpoint of `wallet` on the
+    /// same server.
+    pub(crate) async fn call_on<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        client: &jsonrpc::client::Client,
+        wallet: Option<&str>,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        Ok(self.observe(cmd, wallet, send_jsonrpc(client, cmd, args)).await?.result()?)
+    }
+
+    /// Call a `cmd` rpc with given `args` list on `client` like
+    /// [Client::call_on], returning the result without parsing it.
+    pub(crate) async fn call_raw_on(
+        &self,
+        client: &jsonrpc::client::Client,
+        wallet: Option<&str>,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<Box<RawValue>> {
+        raw_result(self.observe(cmd, wallet, send_jsonrpc(client, cmd, args)).await?)
+    }
+
+    /// Call a `cmd` rpc with given named `args` on `endpoint`, which is
+    /// either the endpoint of `self` or the endpoint of `wallet` on the same
+    /// server.
+    pub(crate) async fn call_named_on<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        endpoint: &Endpoint,
+        wallet: Option<&str>,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        let req = NamedRequest {
+            method: cmd,
+            params: args,
+            id: self.client.build_request(cmd, &[]).id,
+            jsonrpc: Some("2.0"),
+        };
+        if log_enabled!(Debug) {
+            debug!(target: "bitcoincore_rpc", "JSON-RPC request: {} {}", cmd, redact_named(cmd, args));
+        }
+
+        let send = async {
+            #[cfg(feature = "tracing")]
+            spans::record_named_request(&req.id, cmd, args);
+            let resp = endpoint.send_named(&req).await?;
+            if let Some(ref cassette) = self.cassette {
+                cassette.record(endpoint.path(), &req, &resp)?;
+            }
+            Ok::<_, Error>(resp)
+        };
+        let resp = self.observe(cmd, wallet, send).await;
+        log_response(cmd, &resp);
+        Ok(resp?.result()?)
+    }
+
+    /// Wait for `call` of the `cmd` rpc with [Client::with_timeout],
+    /// reporting it to the metrics of the client and, with the `tracing`
+    /// feature, in a span.
+    async fn observe(
+        &self,
+        cmd: &str,
+        wallet: Option<&str>,
+        call: impl Future<Output = Result<jsonrpc::Response>>,
+    ) -> Result<jsonrpc::Response> {
+        self.report(cmd, wallet, self.with_timeout(cmd, call)).await
+    }
+
+    /// Wait for `call` of the `cmd` rpc, reporting it to the metrics of the
+    /// client and, with the `tracing` feature, in a span.
+    pub(crate) async fn report(
+        &self,
+        cmd: &str,
+        wallet: Option<&str>,
+        call: impl Future<Output = Result<jsonrpc::Response>>,
+    ) -> Result<jsonrpc::Response> {
+        #[cfg(feature = "tracing")]
+        let (span, start) = (spans::call_span(cmd, wallet), std::time::Instant::now());
+        #[cfg(feature = "tracing")]
+        let call = tracing::Instrument::instrument(call, span.clone());
+        #[cfg(not(feature = "tracing"))]
+        let _ = wallet;
+
+        let resp = match self.metrics {
+            Some(ref metrics) => {
+                let guard = CallGuard::start(&**metrics, cmd);
+                let resp = call.await;
+                match resp {
+                    Ok(jsonrpc::Response {
+                        error: Some(ref e),
+                        ..
+                    }) => {
+                        let err = Error::JsonRpc(jsonrpc::Error::Rpc(e.clone()));
+                        guard.finish(CallOutcome::Error(&err));
+                    }
+                    Ok(_) => guard.finish(CallOutcome::Success),
+                    Err(ref e) => guard.finish(CallOutcome::Error(e)),
+                }
+                resp
+            }
+            None => call.await,
+        };
+        #[cfg(feature = "tracing")]
+        spans::record_response(&span, start.elapsed(), &resp);
+        resp
+    }
+
+    /// Wait for `call` of the `cmd` rpc, failing with [Error::Timeout] if it
+    /// takes longer than the timeout for `cmd`.
+    async fn with_timeout<T>(&self, cmd: &str, call: impl Future<Output = Result<T>>) -> Result<T> {
+        match self.timeout(cmd) {
+            Some(timeout) => rt::timeout(timeout, call).await.map_err(|_| {
+                debug!(target: "bitcoincore_rpc", "JSON-RPC request {} timed out after {:?}", cmd, timeout);
+                Error::Timeout
+            })?,
+            None => call.await,
+        }
+    }
+
+    /// Statistics of the connection pool of the transport.
+    ///
+    /// [None] if the client was not created from a URL or its transport
+    /// doesn't have a pool.
+    pub fn pool_stats(&self) -> Option<PoolStats> {
+        self.endpoint.as_ref()?.http().stats()
+    }
+
+    /// Start a new [Batch] of calls that are sent together.
+    pub fn batch(&self) -> Batch<'_> {
+        Batch::new(self)
+    }
+
+    /// Get a client for the REST interface of the same server, with the
+    /// default timeout of this client.
+    ///
+    /// [None] if the client was not created from a URL.
+    pub fn rest(&self) -> Option<RestClient> {
+        let endpoint = self.endpoint.as_ref()?;
+        Some(RestClient::from_endpoint(endpoint, self.timeouts.default))
+    }
+
+    /// Get a handle for the wallet `name` on a node with multiple wallets.
+    ///
+    /// The handle shares the transport and authentication of this client.
+    pub fn wallet(&self, name: &str) -> WalletClient<'_> {
+        let wallet = self.endpoint.as_ref().map(|endpoint| {
+            let endpoint = endpoint.wallet(name);
+            (jsonrpc_client(endpoint.clone(), &self.cassette), endpoint)
+        });
+        WalletClient::new(self, name, wallet)
+    }
 }
 
 #[async_trait]
@@ -1123,22 +1513,77 @@ impl RpcApi for Client {
         cmd: &str,
         args: &[serde_json::Value],
     ) -> Result<T> {
-        let v_args: Vec<_> = args
-            .iter()
-            .map(serde_json::value::to_raw_value)
-            .collect::<std::result::Result<_, serde_json::Error>>()?;
-        let req = self.client.build_request(cmd, &v_args[..]);
-        if log_enabled!(Debug) {
-            debug!(target: "bitcoincore_rpc", "JSON-RPC request: {} {}", cmd, serde_json::Value::from(args));
+        self.call_on(&self.client, None, cmd, args).await
+    }
+
+    async fn call_raw(&self, cmd: &str, args: &[serde_json::Value]) -> Result<Box<RawValue>> {
+        self.call_raw_on(&self.client, None, cmd, args).await
+    }
+
+    /// Call an `cmd` rpc with given named `args`
+    ///
+    /// Fails with [Error::NamedParamsUnsupported] if the client was not
+    /// created from a URL.
+    async fn call_named<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        match self.endpoint {
+            Some(ref endpoint) => self.call_named_on(endpoint, None, cmd, args).await,
+            None => Err(Error::NamedParamsUnsupported),
         }
+    }
 
-        let resp = self.client.send_request(req).await.map_err(Error::from);
-        log_response(cmd, &resp);
-        Ok(resp?.result()?)
+    fn named_params(&self) -> bool {
+        self.named_params
+    }
+}
+
+/// Create a JSONRPC client for `endpoint`, recording to `cassette` if set.
+fn jsonrpc_client(endpoint: Endpoint, cassette: &Option<Arc<Cassette>>) -> jsonrpc::client::Client {
+    match *cassette {
+        Some(ref cassette) => jsonrpc::client::Client::with_transport(
+            RecordingTransport::with_cassette(endpoint.clone(), cassette.clone(), endpoint.path()),
+        ),
+        None => jsonrpc::client::Client::with_transport(endpoint),
     }
 }
 
-fn log_response(cmd: &str, resp: &Result<jsonrpc::Response>) {
+/// Send a `cmd` rpc with given `args` list on a JSONRPC client.
+async fn send_jsonrpc(
+    client: &jsonrpc::client::Client,
+    cmd: &str,
+    args: &[serde_json::Value],
+) -> Result<jsonrpc::Response> {
+    let v_args: Vec<_> = args
+        .iter()
+        .map(serde_json::value::to_raw_value)
+        .collect::<std::result::Result<_, serde_json::Error>>()?;
+    let req = client.build_request(cmd, &v_args[..]);
+    #[cfg(feature = "tracing")]
+    spans::record_request(&req.id, cmd, args);
+    if log_enabled!(Debug) {
+        debug!(target: "bitcoincore_rpc", "JSON-RPC request: {} {}", cmd, redact_positional(cmd, args));
+    }
+
+    let resp = client.send_request(req).await.map_err(Error::from);
+    log_response(cmd, &resp);
+    resp
+}
+
+/// Take the result out of `resp` without parsing it, `null` if there is none.
+fn raw_result(resp: jsonrpc::Response) -> Result<Box<RawValue>> {
+    if let Some(e) = resp.error {
+        return Err(Error::JsonRpc(jsonrpc::Error::Rpc(e)));
+    }
+    match resp.result {
+        Some(result) => Ok(result),
+        None => Ok(serde_json::value::to_raw_value(&serde_json::Value::Null)?),
+    }
+}
+
+pub(crate) fn log_response(cmd: &str, resp: &Result<jsonrpc::Response>) {
     if log_enabled!(Warn) || log_enabled!(Debug) || log_enabled!(Trace) {
         match resp {
             Err(ref e) => {
@@ -1165,6 +1610,7 @@ fn log_response(cmd: &str, resp: &Result<jsonrpc::Response>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_server::serve;
     use bitcoin;
     use serde_json;
     use tokio;
@@ -1237,4 +1683,163 @@ mod tests {
     fn test_handle_defaults() {
         test_handle_defaults_inner().unwrap();
     }
+
+    #[test]
+    fn test_param_names_sorted() {
+        assert!(PARAM_NAMES.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[tokio::test]
+    async fn test_named_params() {
+        // Reply with the params of each request as result.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve(listener, 2, |req| {
+            let req = req.json();
+            (
+                200,
+                serde_json::json!({"result": req["params"], "error": null, "id": req["id"]})
+                    .to_string(),
+            )
+        }));
+
+        let client = ClientBuilder::new(url.clone(), Auth::None).build().await.unwrap();
+        let mut args = ["*".into(), null(), into_json(true).unwrap()];
+        let params: serde_json::Value =
+            call_optional(&client, "getbalance", &mut args, &[0.into(), null()]).await.unwrap();
+        assert_eq!(params, serde_json::json!(["*", 0, true]));
+
+        let client = ClientBuilder::new(url, Auth::None).named_params(true).build().await.unwrap();
+        let mut args = ["*".into(), null(), into_json(true).unwrap()];
+        let params: serde_json::Value =
+            call_optional(&client, "getbalance", &mut args, &[0.into(), null()]).await.unwrap();
+        assert_eq!(params, serde_json::json!({"dummy": "*", "include_watchonly": true}));
+        server.await.unwrap();
+
+        let transport = jsonrpc::simple_http::SimpleHttpTransport::new();
+        let client = Client::from_jsonrpc(jsonrpc::client::Client::with_transport(transport));
+        let err = client.call_named::<()>("getbalance", &Default::default()).await.unwrap_err();
+        assert!(matches!(err, Error::NamedParamsUnsupported));
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_named_params_of_all_methods() {
+        use bitcoin::hashes::Hash;
+
+        let mock = crate::MockRpc::new().named_params(true);
+        for &(cmd, _) in PARAM_NAMES {
+            mock.expect(cmd).repeatedly();
+        }
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let privkey =
+            PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let pubkey = PublicKey::from_private_key(&secp, &privkey);
+        let address = Address::p2pkh(&pubkey, bitcoin::Network::Regtest);
+        let txid = bitcoin::Txid::all_zeros();
+        let outs = HashMap::new();
+
+        // Only check that every method passes as many arguments as it has
+        // names, the results don't parse from `null`.
+        let _ = mock.add_multisig_address(1, &[], None, None).await;
+        let _ = mock.unload_wallet(None).await;
+        let _ = mock.create_wallet("w", None, None, None, None).await;
+        let _ = mock.backup_wallet(None).await;
+        let _ = mock.get_raw_transaction_info(&txid, None).await;
+        let _ = mock.get_balance(None, None).await;
+        let _ = mock.get_received_by_address(&address, None).await;
+        let _ = mock.get_transaction(&txid, None).await;
+        let _ = mock.list_transactions(None, None, None, None).await;
+        let _ = mock.list_since_block(None, None, None, None).await;
+        let _ = mock.get_tx_out(&txid, 0, None).await;
+        let _ = mock.get_tx_out_proof(&[txid], None).await;
+        let _ = mock.import_public_key(&pubkey, None, None).await;
+        let _ = mock.import_private_key(&privkey, None, None).await;
+        let _ = mock.import_address(&address, None, None).await;
+        let _ = mock.import_address_script(&address.script_pubkey(), None, None, None).await;
+        let _ = mock.import_multi(&[], None).await;
+        let _ = mock.key_pool_refill(None).await;
+        let _ = mock.list_unspent(None, None, None, None, None).await;
+        let _ = mock.list_received_by_address(None, None, None, None).await;
+        let _ = mock.create_raw_transaction_hex(&[], &outs, None, None).await;
+        let _ = mock.fund_raw_transaction("00", None, None).await;
+        #[allow(deprecated)]
+        let _ = mock.sign_raw_transaction("00", None, None, None).await;
+        let _ = mock.sign_raw_transaction_with_wallet("00", None, None).await;
+        let _ = mock.sign_raw_transaction_with_key("00", &[privkey], None, None).await;
+        let _ = mock.get_new_address(None, None).await;
+        let _ = mock.generate(1, None).await;
+        let _ = mock
+            .send_to_address(&address, Amount::ONE_BTC, None, None, None, None, None, None)
+            .await;
+        let _ = mock.estimate_smart_fee(6, None).await;
+        let _ = mock.wallet_create_funded_psbt(&[], &outs, None, None, None).await;
+        let _ = mock.finalize_psbt("", None).await;
+        let _ = mock.derive_addresses("", None).await;
+        let _ = mock.rescan_blockchain(None, None).await;
+        let _ = mock.get_network_hash_ps(None, None).await;
+
+        let called: std::collections::HashSet<_> =
+            mock.calls().into_iter().map(|(cmd, _)| cmd).collect();
+        for &(cmd, _) in PARAM_NAMES {
+            assert!(called.contains(cmd), "{} was not called", cmd);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_optional_positional_params() {
+        // Record the params of each request and reply with a result that
+        // parses for both methods.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let params = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = params.clone();
+        let server = tokio::spawn(serve(listener, 4, move |req| {
+            let req = req.json();
+            seen.lock().unwrap().push(req["params"].clone());
+            let result = match req["method"].as_str() {
+                Some("getnewaddress") => "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".into(),
+                _ => serde_json::json!([]),
+            };
+            (200, serde_json::json!({"result": result, "error": null, "id": req["id"]}).to_string())
+        }));
+
+        // Positional calls send every argument, nulls included.
+        let client = Client::new(url.clone(), Auth::None).await.unwrap();
+        client.get_new_address(None, Some(json::AddressType::Bech32)).await.unwrap();
+        client.generate(1, None).await.unwrap();
+        let client = ClientBuilder::new(url, Auth::None).named_params(true).build().await.unwrap();
+        client.get_new_address(None, Some(json::AddressType::Bech32)).await.unwrap();
+        client.generate(1, None).await.unwrap();
+        server.await.unwrap();
+        assert_eq!(
+            *params.lock().unwrap(),
+            [
+                serde_json::json!([null, "bech32"]),
+                serde_json::json!([1, null]),
+                serde_json::json!({"address_type": "bech32"}),
+                serde_json::json!({"nblocks": 1}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_call_raw() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve(listener, 2, |req| match req.json()["method"].as_str() {
+            Some("getrawmempool") => {
+                (200, r#"{"result": {"ab": {"vsize": 141}}, "error": null, "id": 1}"#)
+            }
+            _ => (200, r#"{"result": null, "error": null, "id": 2}"#),
+        }));
+
+        let client = Client::new(url, Auth::None).await.unwrap();
+        // The result is returned as received.
+        let raw = client.call_raw("getrawmempool", &[true.into()]).await.unwrap();
+        assert_eq!(raw.get(), r#"{"ab": {"vsize": 141}}"#);
+        let raw = client.call_raw("ping", &[]).await.unwrap();
+        assert_eq!(raw.get(), "null");
+        server.await.unwrap();
+    }
 }
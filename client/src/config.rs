@@ -0,0 +1,260 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Reading the RPC settings from a `bitcoin.conf` file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bitcoin::Network;
+use crate::client::{Auth, Result};
+use crate::error::Error;
+
+/// Options that are ignored outside of a network section for networks other
+/// than mainnet, just like Bitcoin Core does.
+const NETWORK_ONLY_OPTIONS: &[&str] = &["rpcport"];
+
+/// The default RPC port of `network`.
+pub fn default_rpc_port(network: Network) -> u16 {
+    match network {
+        Network::Bitcoin => 8332,
+        Network::Testnet => 18332,
+        Network::Signet => 38332,
+        Network::Regtest => 18443,
+    }
+}
+
+/// The name of the `bitcoin.conf` section with the settings for `network`.
+fn section_name(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "main",
+        Network::Testnet => "test",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+    }
+}
+
+/// The directory in `datadir` where bitcoind keeps the data of `network`,
+/// including the `.cookie` file.
+pub fn network_dir(datadir: &Path, network: Network) -> PathBuf {
+    match network {
+        Network::Bitcoin => datadir.to_owned(),
+        Network::Testnet => datadir.join("testnet3"),
+        Network::Signet => datadir.join("signet"),
+        Network::Regtest => datadir.join("regtest"),
+    }
+}
+
+/// The RPC client settings of a `bitcoin.conf` file, for a single network.
+///
+/// These are the settings `bitcoin-cli` uses to connect, the settings of the
+/// server like `rpcbind` are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RpcConfig {
+    /// The data directory, overriding the one passed to [RpcConfig::auth].
+    pub datadir: Option<PathBuf>,
+    /// The host of the server, optionally with a port.
+    pub rpcconnect: Option<String>,
+    /// The port of the server, overriding the port of `rpcconnect`.
+    pub rpcport: Option<u16>,
+    /// The user name for password authentication.
+    pub rpcuser: Option<String>,
+    /// The password for password authentication, which is used instead of
+    /// the cookie file if set.
+    pub rpcpassword: Option<String>,
+    /// The cookie file, relative to the network directory unless absolute.
+    pub rpccookiefile: Option<PathBuf>,
+}
+
+impl RpcConfig {
+    /// Parse the contents of a `bitcoin.conf` file for `network`.
+    ///
+    /// Settings in the `[main]`, `[test]`, `[signet]` or `[regtest]` section
+    /// of `network` take precedence over settings outside of any section.
+    /// Settings for other networks are ignored.
+    pub fn parse(conf: &str, network: Network) -> Result<RpcConfig> {
+        let mut global = RpcConfig::default();
+        let mut network_config = RpcConfig::default();
+        let mut section: Option<&str> = None;
+        for (i, line) in conf.lines().enumerate() {
+            let err = |reason: &str| Error::InvalidConfFile(format!("line {}: {}", i + 1, reason));
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                if !line.ends_with(']') {
+                    return Err(err("unterminated section name"));
+                }
+                section = Some(line[1..line.len() - 1].trim());
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| err("expected key=value"))?;
+            let (key, value) = (key.trim(), value.trim());
+            // `regtest.rpcport=1` is the same as `rpcport=1` in a `[regtest]` section.
+            let (key_section, key) = match key.split_once('.') {
+                Some((s, k)) => (Some(s), k),
+                None => (section, key),
+            };
+            let config = match key_section {
+                Some(s) if s == section_name(network) => &mut network_config,
+                Some(_) => continue,
+                None if network != Network::Bitcoin && NETWORK_ONLY_OPTIONS.contains(&key) => {
+                    continue
+                }
+                None => &mut global,
+            };
+            config.set(key, value).map_err(err)?;
+        }
+        Ok(network_config.or(global))
+    }
+
+    /// Read and parse a `bitcoin.conf` file for `network`.
+    pub fn read(path: &Path, network: Network) -> Result<RpcConfig> {
+        RpcConfig::parse(&fs::read_to_string(path)?, network)
+    }
+
+    /// Set `key` to `value`, unless `key` was set before.
+    fn set(&mut self, key: &str, value: &str) -> std::result::Result<(), &'static str> {
+        fn set_once<T>(option: &mut Option<T>, value: T) {
+            if option.is_none() {
+                *option = Some(value);
+            }
+        }
+        match key {
+            "datadir" => set_once(&mut self.datadir, value.into()),
+            "rpcconnect" => set_once(&mut self.rpcconnect, value.into()),
+            "rpcport" => set_once(&mut self.rpcport, value.parse().map_err(|_| "invalid rpcport")?),
+            "rpcuser" => set_once(&mut self.rpcuser, value.into()),
+            "rpcpassword" => set_once(&mut self.rpcpassword, value.into()),
+            "rpccookiefile" => set_once(&mut self.rpccookiefile, value.into()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Fill the settings missing in `self` with those of `other`.
+    fn or(self, other: RpcConfig) -> RpcConfig {
+        RpcConfig {
+            datadir: self.datadir.or(other.datadir),
+            rpcconnect: self.rpcconnect.or(other.rpcconnect),
+            rpcport: self.rpcport.or(other.rpcport),
+            rpcuser: self.rpcuser.or(other.rpcuser),
+            rpcpassword: self.rpcpassword.or(other.rpcpassword),
+            rpccookiefile: self.rpccookiefile.or(other.rpccookiefile),
+        }
+    }
+
+    /// The URL of the RPC server of `network`.
+    ///
+    /// Connects to `rpcconnect` if set and to 127.0.0.1 otherwise, like
+    /// `bitcoin-cli`, using `rpcport`, the port of `rpcconnect` or the
+    /// default port of `network`. IPv6 addresses may be given with or
+    /// without brackets.
+    pub fn url(&self, network: Network) -> String {
+        let (host, port) = match self.rpcconnect {
+            Some(ref connect) => split_host_port(connect),
+            None => ("127.0.0.1", None),
+        };
+        let port = self.rpcport.or(port).unwrap_or_else(|| default_rpc_port(network));
+        match host.contains(':') && !host.starts_with('[') {
+            true => format!("http://[{}]:{}", host, port),
+            false => format!("http://{}:{}", host, port),
+        }
+    }
+
+    /// The authentication to use for the RPC server of `network`.
+    ///
+    /// Uses `rpcuser` and `rpcpassword` if set and the cookie file otherwise,
+    /// which is `<datadir>/<network>/.cookie` by default. A relative
+    /// `rpccookiefile` is relative to the network directory.
+    pub fn auth(&self, datadir: &Path, network: Network) -> Auth {
+        if let Some(ref pass) = self.rpcpassword {
+            return Auth::UserPass(self.rpcuser.clone().unwrap_or_default(), pass.clone());
+        }
+        let network_dir = network_dir(self.datadir.as_deref().unwrap_or(datadir), network);
+        match self.rpccookiefile {
+            Some(ref cookie) => Auth::CookieFile(network_dir.join(cookie)),
+            None => Auth::CookieFile(network_dir.join(".cookie")),
+        }
+    }
+}
+
+/// Split `host:port` into host and port, if the port is valid.
+fn split_host_port(s: &str) -> (&str, Option<u16>) {
+    let port_sep = match s.rfind(']') {
+        // Bracketed IPv6 address
+        Some(end) => s[end..].rfind(':').map(|i| end + i),
+        None if s.matches(':').count() == 1 => s.rfind(':'),
+        None => None,
+    };
+    match port_sep.map(|i| (&s[..i], s[i + 1..].parse())) {
+        Some((host, Ok(port))) => (host, Some(port)),
+        _ => (s, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONF: &str = "
+        # Global settings
+        rpcuser=alice
+        rpcpassword=secret # inline comment
+        rpcport=1234
+
+        [regtest]
+        rpcbind=0.0.0.0
+        rpcconnect=127.0.0.2:5678
+        rpcuser=bob
+
+        [test]
+        rpcport=4321
+        main.rpcconnect=10.0.0.1
+    ";
+
+    #[test]
+    fn test_parse() {
+        let main = RpcConfig::parse(CONF, Network::Bitcoin).unwrap();
+        assert_eq!(main.url(Network::Bitcoin), "http://10.0.0.1:1234");
+        assert_eq!(
+            main.auth(Path::new("/data"), Network::Bitcoin),
+            Auth::UserPass("alice".into(), "secret".into())
+        );
+
+        // The global rpcport is ignored for non-mainnet networks, and
+        // rpcbind is a setting of the server only.
+        let regtest = RpcConfig::parse(CONF, Network::Regtest).unwrap();
+        assert_eq!(regtest.url(Network::Regtest), "http://127.0.0.2:5678");
+        assert_eq!(regtest.rpcuser.as_deref(), Some("bob"));
+
+        let testnet = RpcConfig::parse(CONF, Network::Testnet).unwrap();
+        assert_eq!(testnet.url(Network::Testnet), "http://127.0.0.1:4321");
+
+        let signet = RpcConfig::parse("", Network::Signet).unwrap();
+        assert_eq!(signet.url(Network::Signet), "http://127.0.0.1:38332");
+        assert_eq!(
+            signet.auth(Path::new("/data"), Network::Signet),
+            Auth::CookieFile("/data/signet/.cookie".into())
+        );
+
+        let bound = RpcConfig::parse("rpcbind=192.168.1.5:1234", Network::Bitcoin).unwrap();
+        assert_eq!(bound.url(Network::Bitcoin), "http://127.0.0.1:8332");
+
+        let ipv6 = RpcConfig::parse("rpcconnect=::1", Network::Bitcoin).unwrap();
+        assert_eq!(ipv6.url(Network::Bitcoin), "http://[::1]:8332");
+        let ipv6 = RpcConfig::parse("rpcconnect=[::1]:1234", Network::Bitcoin).unwrap();
+        assert_eq!(ipv6.url(Network::Bitcoin), "http://[::1]:1234");
+
+        assert!(RpcConfig::parse("rpcport", Network::Bitcoin).is_err());
+        assert!(RpcConfig::parse("rpcport=x", Network::Bitcoin).is_err());
+    }
+}
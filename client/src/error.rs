@@ -29,6 +29,47 @@ pub enum Error {
     InvalidCookieFile,
     /// The JSON result had an unexpected structure.
     UnexpectedStructure,
+    /// No response was received for a call in a batch request.
+    MissingBatchResponse,
+    /// A wallet endpoint was needed, but the client was not created from a URL.
+    WalletEndpointUnavailable,
+    /// The call did not finish before its timeout.
+    Timeout,
+    /// A `bitcoin.conf` file could not be parsed.
+    InvalidConfFile(String),
+    /// Named parameters were used with a client that can't send them.
+    NamedParamsUnsupported,
+    /// The REST interface replied with an HTTP error status and a message.
+    Rest(u16, String),
+    /// A ZMQ connection failed or sent an invalid message.
+    Zmq(String),
+    /// A TLS certificate or key could not be loaded.
+    Tls(String),
+}
+
+impl Error {
+    /// The JSON-RPC error returned by the server, if this is one.
+    pub fn rpc_error(&self) -> Option<&jsonrpc::error::RpcError> {
+        match *self {
+            Error::JsonRpc(jsonrpc::error::Error::Rpc(ref e)) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// The error code of the JSON-RPC error returned by the server, if this is one.
+    pub fn rpc_code(&self) -> Option<RpcErrorCode> {
+        self.rpc_error().map(|e| RpcErrorCode::from_code(e.code))
+    }
+
+    /// Whether the server is still starting up and not ready to serve requests yet.
+    pub fn is_warmup(&self) -> bool {
+        self.rpc_code() == Some(RpcErrorCode::InWarmup)
+    }
+
+    /// Whether the called method does not exist on the server.
+    pub fn is_method_not_found(&self) -> bool {
+        self.rpc_code() == Some(RpcErrorCode::MethodNotFound)
+    }
 }
 
 impl From<jsonrpc::error::Error> for Error {
@@ -85,6 +126,16 @@ impl fmt::Display for Error {
             Error::InvalidAmount(ref e) => write!(f, "invalid amount: {}", e),
             Error::InvalidCookieFile => write!(f, "invalid cookie file"),
             Error::UnexpectedStructure => write!(f, "the JSON result had an unexpected structure"),
+            Error::MissingBatchResponse => write!(f, "no response for call in batch request"),
+            Error::WalletEndpointUnavailable => write!(f, "no wallet endpoint for this client"),
+            Error::Timeout => write!(f, "request timed out"),
+            Error::InvalidConfFile(ref e) => write!(f, "invalid bitcoin.conf file: {}", e),
+            Error::NamedParamsUnsupported => {
+                write!(f, "named parameters are not supported by this client")
+            }
+            Error::Rest(status, ref message) => write!(f, "REST error {}: {}", status, message),
+            Error::Zmq(ref e) => write!(f, "ZMQ error: {}", e),
+            Error::Tls(ref e) => write!(f, "TLS error: {}", e),
         }
     }
 }
@@ -106,3 +157,239 @@ impl error::Error for Error {
         }
     }
 }
+
+/// The error codes used by Bitcoin Core in JSON-RPC error responses.
+///
+/// See `src/rpc/protocol.h` in the Bitcoin Core repository.
+#[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+pub enum RpcErrorCode {
+    // Standard JSON-RPC 2.0 errors
+    /// The JSON sent is not a valid request object.
+    InvalidRequest,
+    /// The method does not exist or is not available.
+    MethodNotFound,
+    /// Invalid method parameters.
+    InvalidParams,
+    /// Internal JSON-RPC error, also used for invalid parameter counts.
+    InternalError,
+    /// Invalid JSON was received by the server.
+    ParseError,
+
+    // General application defined errors
+    /// Exception thrown in command handling.
+    MiscError,
+    /// Unexpected type was passed as parameter.
+    TypeError,
+    /// Invalid address or key.
+    InvalidAddressOrKey,
+    /// Ran out of memory during operation.
+    OutOfMemory,
+    /// Invalid, missing or duplicate parameter.
+    InvalidParameter,
+    /// Database error.
+    DatabaseError,
+    /// Error parsing or validating structure in raw format.
+    DeserializationError,
+    /// General error during transaction or block submission.
+    VerifyError,
+    /// Transaction or block was rejected by network rules.
+    VerifyRejected,
+    /// Transaction already in chain.
+    VerifyAlreadyInChain,
+    /// Client still warming up.
+    InWarmup,
+    /// The RPC or one of its parameters is deprecated.
+    MethodDeprecated,
+
+    // P2P client errors
+    /// Bitcoin is not connected.
+    ClientNotConnected,
+    /// Still downloading initial blocks.
+    ClientInInitialDownload,
+    /// Node is already added.
+    ClientNodeAlreadyAdded,
+    /// Node has not been added before.
+    ClientNodeNotAdded,
+    /// Node to disconnect not found in connected nodes.
+    ClientNodeNotConnected,
+    /// Invalid IP or subnet.
+    ClientInvalidIpOrSubnet,
+    /// No valid connection manager instance found.
+    ClientP2pDisabled,
+    /// Max number of outbound or block-relay connections already open.
+    ClientNodeCapacityReached,
+
+    // Chain errors
+    /// No mempool instance found.
+    ClientMempoolDisabled,
+
+    // Wallet errors
+    /// Unspecified problem with wallet (key not found etc.).
+    WalletError,
+    /// Not enough funds in wallet or account.
+    WalletInsufficientFunds,
+    /// Invalid label name.
+    WalletInvalidLabelName,
+    /// Keypool ran out, call keypoolrefill first.
+    WalletKeypoolRanOut,
+    /// Enter the wallet passphrase with walletpassphrase first.
+    WalletUnlockNeeded,
+    /// The wallet passphrase entered was incorrect.
+    WalletPassphraseIncorrect,
+    /// Command given in wrong wallet encryption state.
+    WalletWrongEncState,
+    /// Failed to encrypt the wallet.
+    WalletEncryptionFailed,
+    /// Wallet is already unlocked.
+    WalletAlreadyUnlocked,
+    /// Invalid wallet specified.
+    WalletNotFound,
+    /// No wallet specified (error when there are multiple wallets loaded).
+    WalletNotSpecified,
+    /// This same wallet is already loaded.
+    WalletAlreadyLoaded,
+    /// There is already a wallet with the same name.
+    WalletAlreadyExists,
+
+    /// An error code not known to this library.
+    Other(i32),
+}
+
+impl RpcErrorCode {
+    /// Get the error for a numeric error code.
+    pub fn from_code(code: i32) -> RpcErrorCode {
+        use RpcErrorCode::*;
+        match code {
+            -32600 => InvalidRequest,
+            -32601 => MethodNotFound,
+            -32602 => InvalidParams,
+            -32603 => InternalError,
+            -32700 => ParseError,
+            -1 => MiscError,
+            -3 => TypeError,
+            -5 => InvalidAddressOrKey,
+            -7 => OutOfMemory,
+            -8 => InvalidParameter,
+            -20 => DatabaseError,
+            -22 => DeserializationError,
+            -25 => VerifyError,
+            -26 => VerifyRejected,
+            -27 => VerifyAlreadyInChain,
+            -28 => InWarmup,
+            -32 => MethodDeprecated,
+            -9 => ClientNotConnected,
+            -10 => ClientInInitialDownload,
+            -23 => ClientNodeAlreadyAdded,
+            -24 => ClientNodeNotAdded,
+            -29 => ClientNodeNotConnected,
+            -30 => ClientInvalidIpOrSubnet,
+            -31 => ClientP2pDisabled,
+            -34 => ClientNodeCapacityReached,
+            -33 => ClientMempoolDisabled,
+            -4 => WalletError,
+            -6 => WalletInsufficientFunds,
+            -11 => WalletInvalidLabelName,
+            -12 => WalletKeypoolRanOut,
+            -13 => WalletUnlockNeeded,
+            -14 => WalletPassphraseIncorrect,
+            -15 => WalletWrongEncState,
+            -16 => WalletEncryptionFailed,
+            -17 => WalletAlreadyUnlocked,
+            -18 => WalletNotFound,
+            -19 => WalletNotSpecified,
+            -35 => WalletAlreadyLoaded,
+            -36 => WalletAlreadyExists,
+            c => Other(c),
+        }
+    }
+
+    /// The numeric error code.
+    pub fn code(self) -> i32 {
+        use RpcErrorCode::*;
+        match self {
+            InvalidRequest => -32600,
+            MethodNotFound => -32601,
+            InvalidParams => -32602,
+            InternalError => -32603,
+            ParseError => -32700,
+            MiscError => -1,
+            TypeError => -3,
+            InvalidAddressOrKey => -5,
+            OutOfMemory => -7,
+            InvalidParameter => -8,
+            DatabaseError => -20,
+            DeserializationError => -22,
+            VerifyError => -25,
+            VerifyRejected => -26,
+            VerifyAlreadyInChain => -27,
+            InWarmup => -28,
+            MethodDeprecated => -32,
+            ClientNotConnected => -9,
+            ClientInInitialDownload => -10,
+            ClientNodeAlreadyAdded => -23,
+            ClientNodeNotAdded => -24,
+            ClientNodeNotConnected => -29,
+            ClientInvalidIpOrSubnet => -30,
+            ClientP2pDisabled => -31,
+            ClientNodeCapacityReached => -34,
+            ClientMempoolDisabled => -33,
+            WalletError => -4,
+            WalletInsufficientFunds => -6,
+            WalletInvalidLabelName => -11,
+            WalletKeypoolRanOut => -12,
+            WalletUnlockNeeded => -13,
+            WalletPassphraseIncorrect => -14,
+            WalletWrongEncState => -15,
+            WalletEncryptionFailed => -16,
+            WalletAlreadyUnlocked => -17,
+            WalletNotFound => -18,
+            WalletNotSpecified => -19,
+            WalletAlreadyLoaded => -35,
+            WalletAlreadyExists => -36,
+            Other(c) => c,
+        }
+    }
+}
+
+impl From<i32> for RpcErrorCode {
+    fn from(code: i32) -> RpcErrorCode {
+        RpcErrorCode::from_code(code)
+    }
+}
+
+impl fmt::Display for RpcErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RpcErrorCode::Other(c) => write!(f, "RPC error code {}", c),
+            c => write!(f, "{:?} ({})", c, c.code()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_error_code_roundtrip() {
+        for code in -40..0 {
+            assert_eq!(RpcErrorCode::from_code(code).code(), code);
+        }
+        for code in -32700..-32600 {
+            assert_eq!(RpcErrorCode::from_code(code).code(), code);
+        }
+        assert_eq!(RpcErrorCode::from_code(-2), RpcErrorCode::Other(-2));
+    }
+
+    #[test]
+    fn test_is_warmup() {
+        let err = Error::JsonRpc(jsonrpc::error::Error::Rpc(jsonrpc::error::RpcError {
+            code: -28,
+            message: "Loading block index...".into(),
+            data: None,
+        }));
+        assert!(err.is_warmup());
+        assert_eq!(err.rpc_code(), Some(RpcErrorCode::InWarmup));
+        assert!(!Error::UnexpectedStructure.is_warmup());
+    }
+}
@@ -0,0 +1,368 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Failing over between several bitcoind nodes.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use futures::future;
+use jsonrpc_async as jsonrpc;
+use log::debug;
+
+use crate::client::{Params, Result, RpcApi};
+use crate::error::Error;
+
+/// Node methods that only read the state of the node and can be answered by
+/// any node that is in sync, so they can be spread over the nodes.
+///
+/// Sorted, so it can be searched with [slice::binary_search].
+const READ_ONLY_METHODS: &[&str] = &[
+    "analyzepsbt",
+    "combinepsbt",
+    "combinerawtransaction",
+    "converttopsbt",
+    "createmultisig",
+    "createpsbt",
+    "createrawtransaction",
+    "decodepsbt",
+    "decoderawtransaction",
+    "decodescript",
+    "deriveaddresses",
+    "estimatesmartfee",
+    "finalizepsbt",
+    "getbestblockhash",
+    "getblock",
+    "getblockchaininfo",
+    "getblockcount",
+    "getblockfilter",
+    "getblockhash",
+    "getblockheader",
+    "getblockstats",
+    "getchaintips",
+    "getchaintxstats",
+    "getdeploymentinfo",
+    "getdescriptorinfo",
+    "getdifficulty",
+    "getindexinfo",
+    "getmempoolancestors",
+    "getmempooldescendants",
+    "getmempoolentry",
+    "getmempoolinfo",
+    "getmininginfo",
+    "getnetworkhashps",
+    "getrawmempool",
+    "getrawtransaction",
+    "gettxout",
+    "gettxoutproof",
+    "gettxoutsetinfo",
+    "joinpsbts",
+    "testmempoolaccept",
+    "utxoupdatepsbt",
+    "validateaddress",
+    "verifymessage",
+    "verifytxoutproof",
+];
+
+/// Whether `cmd` only reads the state of the node.
+fn is_read_only(cmd: &str) -> bool {
+    READ_ONLY_METHODS.binary_search(&cmd).is_ok()
+}
+
+/// A backend node and what the last health check found out about it.
+struct Backend<C> {
+    client: C,
+    healthy: AtomicBool,
+    height: AtomicU64,
+}
+
+/// An [RpcApi] that spreads calls over several nodes and fails over to
+/// another node when one is unreachable.
+///
+/// Calls of methods that only read the state of the chain or the mempool,
+/// like `getblock` or `getrawtransaction`, go to the healthy nodes in turn and are sent to the next node if
+/// the node fails to answer. All other calls, including wallet calls and
+/// calls that change the state of the node like `sendrawtransaction` or
+/// `invalidateblock`, are always sent to the primary node, the first one
+/// passed to [FailoverClient::new]. Call [FailoverClient::check_health]
+/// periodically to find out which nodes are healthy; until then, all nodes
+/// are assumed to be.
+pub struct FailoverClient<C> {
+    backends: Vec<Backend<C>>,
+    /// Nodes more than this many blocks behind the best node are unhealthy.
+    max_lag: u64,
+    /// The backend to send the next read-only call to first.
+    next: AtomicUsize,
+    /// The backend that answered the last call.
+    last: AtomicUsize,
+}
+
+impl<C: RpcApi + Sync> FailoverClient<C> {
+    /// Fail over between `clients`, using the first one as primary.
+    ///
+    /// Panics if `clients` is empty.
+    pub fn new(clients: Vec<C>) -> FailoverClient<C> {
+        assert!(!clients.is_empty(), "a failover client needs at least one node");
+        FailoverClient {
+            backends: clients
+                .into_iter()
+                .map(|client| Backend {
+                    client,
+                    healthy: AtomicBool::new(true),
+                    height: AtomicU64::new(0),
+                })
+                .collect(),
+            max_lag: 2,
+            next: AtomicUsize::new(0),
+            last: AtomicUsize::new(0),
+        }
+    }
+
+    /// Set how many blocks a node may be behind the best node and still be
+    /// considered healthy, 2 if not set.
+    pub fn max_lag(mut self, blocks: u64) -> Self {
+        self.max_lag = blocks;
+        self
+    }
+
+    /// Get the client of node `index`.
+    pub fn backend(&self, index: usize) -> &C {
+        &self.backends[index].client
+    }
+
+    /// Get the client of the primary node.
+    pub fn primary(&self) -> &C {
+        self.backend(0)
+    }
+
+    /// Whether node `index` was healthy at the last health check and hasn't
+    /// failed since.
+    pub fn is_healthy(&self, index: usize) -> bool {
+        self.backends[index].healthy.load(Ordering::Relaxed)
+    }
+
+    /// The block count of node `index` at the last health check.
+    pub fn height(&self, index: usize) -> u64 {
+        self.backends[index].height.load(Ordering::Relaxed)
+    }
+
+    /// The index of the node that answered the last call that was answered,
+    /// also with an error returned by the node.
+    ///
+    /// With concurrent calls, this is the one that finished last.
+    pub fn last_backend(&self) -> usize {
+        self.last.load(Ordering::Relaxed)
+    }
+
+    /// Ping all nodes and ask them for their block count, and mark those
+    /// that don't answer or lag behind the best node as unhealthy, the others
+    /// as healthy.
+    pub async fn check_health(&self) {
+        let heights = future::join_all(self.backends.iter().map(|b| async move {
+            b.client.ping().await?;
+            b.client.get_block_count().await
+        }))
+        .await;
+        let best = heights.iter().filter_map(|h| h.as_ref().ok()).max().copied().unwrap_or(0);
+        for (index, (backend, height)) in self.backends.iter().zip(heights).enumerate() {
+            let healthy = match height {
+                Ok(height) => {
+                    backend.height.store(height, Ordering::Relaxed);
+                    height + self.max_lag >= best
+                }
+                Err(e) => {
+                    debug!(target: "bitcoincore_rpc", "failover node {} is unhealthy: {}", index, e);
+                    false
+                }
+            };
+            backend.healthy.store(healthy, Ordering::Relaxed);
+        }
+    }
+
+    /// Call a `cmd` rpc with given `args` list, returning the result and the
+    /// index of the node that served it.
+    pub async fn call_with_backend<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<(T, usize)> {
+        self.route(cmd, Params::Positional(args)).await
+    }
+
+    /// Send a call to the node it should go to, trying the other nodes if
+    /// that one fails.
+    async fn route<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        params: Params<'_>,
+    ) -> Result<(T, usize)> {
+        if !is_read_only(cmd) {
+            let res = params.call(self.primary(), cmd).await;
+            match res {
+                Err(ref e) if is_node_failure(e) => {}
+                _ => self.last.store(0, Ordering::Relaxed),
+            }
+            return res.map(|r| (r, 0));
+        }
+
+        // Try the healthy nodes first, starting at the next one in turn.
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let n = self.backends.len();
+        let mut order: Vec<usize> = (0..n).map(|i| (start + i) % n).collect();
+        order.sort_by_key(|&i| !self.is_healthy(i));
+
+        let mut last_err = None;
+        for index in order {
+            let backend = &self.backends[index];
+            match params.call(&backend.client, cmd).await {
+                Err(e) if is_node_failure(&e) => {
+                    debug!(target: "bitcoincore_rpc", "failover node {} failed for {}: {}", index, cmd, e);
+                    backend.healthy.store(false, Ordering::Relaxed);
+                    last_err = Some(e);
+                }
+                res => {
+                    self.last.store(index, Ordering::Relaxed);
+                    return res.map(|r| (r, index));
+                }
+            }
+        }
+        Err(last_err.expect("at least one node"))
+    }
+}
+
+/// Whether `err` means that the node didn't answer, rather than that the
+/// call itself failed.
+fn is_node_failure(err: &Error) -> bool {
+    match *err {
+        Error::Timeout => true,
+        Error::JsonRpc(jsonrpc::Error::Transport(_)) => true,
+        ref e => e.is_warmup(),
+    }
+}
+
+#[async_trait]
+impl<C: RpcApi + Sync> RpcApi for FailoverClient<C> {
+    async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        Ok(self.call_with_backend(cmd, args).await?.0)
+    }
+
+    async fn call_named<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        Ok(self.route(cmd, Params::Named(args)).await?.0)
+    }
+
+    /// Whether the primary node uses named parameters.
+    fn named_params(&self) -> bool {
+        self.primary().named_params()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc::simple_http;
+    use std::sync::Mutex;
+
+    /// A node at `height` that fails every call if it is `down`, recording
+    /// the methods it was called with.
+    struct Node {
+        height: u64,
+        down: bool,
+        calls: Mutex<Vec<String>>,
+    }
+
+    fn node(height: u64, down: bool) -> Node {
+        Node {
+            height,
+            down,
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[async_trait]
+    impl RpcApi for Node {
+        async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            _: &[serde_json::Value],
+        ) -> Result<T> {
+            self.calls.lock().unwrap().push(cmd.to_owned());
+            if self.down {
+                let err = simple_http::Error::HttpErrorCode(503);
+                Err(Error::JsonRpc(jsonrpc::Error::Transport(Box::new(err))))
+            } else if cmd == "ping" {
+                Ok(serde_json::from_value(serde_json::Value::Null)?)
+            } else {
+                Ok(serde_json::from_value(self.height.into())?)
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_only_methods_sorted() {
+        assert!(READ_ONLY_METHODS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let client = FailoverClient::new(vec![node(100, true), node(90, false), node(100, false)]);
+        client.check_health().await;
+        assert!(!client.is_healthy(0));
+        assert!(!client.is_healthy(1));
+        assert!(client.is_healthy(2));
+        assert_eq!(*client.backend(2).calls.lock().unwrap(), ["ping", "getblockcount"]);
+        // A node that doesn't answer the ping isn't asked for its block count.
+        assert_eq!(*client.backend(0).calls.lock().unwrap(), ["ping"]);
+        for _ in 0..3 {
+            assert_eq!(
+                client.call_with_backend::<u64>("getblockcount", &[]).await.unwrap(),
+                (100, 2)
+            );
+        }
+
+        // Without a health check, the down node is found out by calling it.
+        let client = FailoverClient::new(vec![node(100, true), node(100, false)]);
+        assert_eq!(client.get_block_count().await.unwrap(), 100);
+        assert_eq!(client.last_backend(), 1);
+        assert!(!client.is_healthy(0));
+    }
+
+    #[tokio::test]
+    async fn test_pinned_to_primary() {
+        let client = FailoverClient::new(vec![node(100, false), node(100, false)]);
+        client.get_block_count().await.unwrap();
+        client.get_block_count().await.unwrap();
+        assert_eq!(client.last_backend(), 1);
+
+        // Calls that change the state of the node, and wallet calls, are
+        // only sent to the primary node.
+        for cmd in ["invalidateblock", "sendrawtransaction", "setban", "getbalance"].iter() {
+            assert_eq!(client.call_with_backend::<u64>(cmd, &[]).await.unwrap(), (100, 0));
+            assert_eq!(client.last_backend(), 0);
+        }
+        assert_eq!(
+            *client.backend(0).calls.lock().unwrap(),
+            ["getblockcount", "invalidateblock", "sendrawtransaction", "setban", "getbalance"]
+        );
+        assert_eq!(*client.backend(1).calls.lock().unwrap(), ["getblockcount"]);
+
+        // The primary node is not failed over from for those calls.
+        let client = FailoverClient::new(vec![node(100, true), node(100, false)]);
+        assert!(client.call_with_backend::<u64>("submitblock", &[]).await.is_err());
+        assert!(client.backend(1).calls.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,347 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Following the best chain block by block, including reorgs.
+
+use std::time::Duration;
+
+use futures::stream::{self, Stream};
+use log::debug;
+
+use crate::bitcoin::{Block, BlockHash};
+use crate::client::{Result, RpcApi};
+use crate::json::BlockRef;
+
+/// A change of the best chain reported by a [ChainFollower].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// The block was added to the tip of the best chain.
+    BlockConnected(Block),
+    /// The block at the tip was removed from the best chain by a reorg.
+    BlockDisconnected(BlockHash),
+}
+
+/// Where the follower continues.
+#[derive(Clone, Debug)]
+enum Position {
+    /// Connect the block at this height first.
+    Height(u64),
+    /// Connect the block with this hash first.
+    Hash(BlockHash),
+    /// The last reported block, which is connected unless it was reorged out.
+    After(BlockRef),
+}
+
+/// Reports the blocks that are connected to and disconnected from the best
+/// chain of a node, one at a time and in order.
+///
+/// When a reorg happens, the blocks of the old chain are disconnected from
+/// the tip down to the fork point before the blocks of the new chain are
+/// connected. Once the follower is at the tip, it waits for new blocks with
+/// [RpcApi::wait_for_new_block].
+///
+/// The follower can be resumed after a restart by saving its
+/// [ChainFollower::checkpoint] after handling each event and creating the
+/// new follower with [ChainFollower::from_checkpoint].
+///
+/// ```no_run
+/// # async fn example(client: &bitcoincore_rpc_async::Client) -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::{ChainEvent, ChainFollower};
+///
+/// let mut follower = ChainFollower::from_height(client, 700_000);
+/// loop {
+///     match follower.next_event().await? {
+///         ChainEvent::BlockConnected(block) => println!("connected {}", block.block_hash()),
+///         ChainEvent::BlockDisconnected(hash) => println!("disconnected {}", hash),
+///     }
+/// }
+/// # }
+/// ```
+pub struct ChainFollower<'a, C> {
+    client: &'a C,
+    position: Position,
+    wait_timeout: Duration,
+}
+
+impl<'a, C: RpcApi + Sync> ChainFollower<'a, C> {
+    fn new(client: &'a C, position: Position) -> ChainFollower<'a, C> {
+        ChainFollower {
+            client,
+            position,
+            wait_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Follow the best chain, starting by connecting the block at `height`.
+    ///
+    /// If `height` is above the tip, the follower waits for the block.
+    pub fn from_height(client: &'a C, height: u64) -> ChainFollower<'a, C> {
+        ChainFollower::new(client, Position::Height(height))
+    }
+
+    /// Follow the best chain, starting by connecting the block `hash`.
+    ///
+    /// If `hash` is not in the best chain, it is disconnected again right
+    /// after.
+    pub fn from_hash(client: &'a C, hash: BlockHash) -> ChainFollower<'a, C> {
+        ChainFollower::new(client, Position::Hash(hash))
+    }
+
+    /// Resume following the best chain after the block of `checkpoint`,
+    /// which was the last block reported before.
+    pub fn from_checkpoint(client: &'a C, checkpoint: BlockRef) -> ChainFollower<'a, C> {
+        ChainFollower::new(client, Position::After(checkpoint))
+    }
+
+    /// Set how long to wait for a new block at the tip before checking the
+    /// chain again, 10 seconds if not set.
+    ///
+    /// This should be shorter than the timeout of the client.
+    pub fn wait_timeout(mut self, timeout: Duration) -> Self {
+        self.wait_timeout = timeout;
+        self
+    }
+
+    /// The last block that was connected, or the block below the last one
+    /// that was disconnected. [None] before the first event.
+    pub fn checkpoint(&self) -> Option<BlockRef> {
+        match self.position {
+            Position::After(ref block) => Some(block.clone()),
+            _ => None,
+        }
+    }
+
+    /// Wait for the next change of the best chain.
+    ///
+    /// If this returns [Err], the follower stays at its position, so calling
+    /// it again retries.
+    pub async fn next_event(&mut self) -> Result<ChainEvent> {
+        loop {
+            let timeout = self.wait_timeout.as_millis() as u64;
+            let tip = match self.position {
+                Position::Height(height) => {
+                    if height > self.client.get_block_count().await? {
+                        // The block wasn't mined yet.
+                        self.client.wait_for_new_block(timeout).await?;
+                        continue;
+                    }
+                    let hash = self.client.get_block_hash(height).await?;
+                    return self.connect(hash, height).await;
+                }
+                Position::Hash(hash) => {
+                    let header = self.client.get_block_header_info(&hash).await?;
+                    return self.connect(hash, header.height as u64).await;
+                }
+                Position::After(ref tip) => tip.clone(),
+            };
+
+            let header = self.client.get_block_header_info(&tip.hash).await?;
+            if header.confirmations < 0 {
+                // The block is no longer in the best chain, walk back towards
+                // the fork point.
+                if let Some(prev) = header.previous_block_hash {
+                    debug!(target: "bitcoincore_rpc", "chain follower disconnected block {}", tip.hash);
+                    self.position = Position::After(BlockRef {
+                        hash: prev,
+                        height: tip.height - 1,
+                    });
+                    return Ok(ChainEvent::BlockDisconnected(tip.hash));
+                }
+            }
+            if let Some(next) = header.next_block_hash {
+                return self.connect(next, tip.height + 1).await;
+            }
+
+            self.client.wait_for_new_block(timeout).await?;
+        }
+    }
+
+    async fn connect(&mut self, hash: BlockHash, height: u64) -> Result<ChainEvent> {
+        let block = self.client.get_block(&hash).await?;
+        debug!(target: "bitcoincore_rpc", "chain follower connected block {} at height {}", hash, height);
+        self.position = Position::After(BlockRef {
+            hash,
+            height,
+        });
+        Ok(ChainEvent::BlockConnected(block))
+    }
+
+    /// Turn the follower into an endless [Stream] of its events.
+    ///
+    /// Errors don't end the stream; polling it again after an error retries.
+    pub fn into_stream(self) -> impl Stream<Item = Result<ChainEvent>> + 'a {
+        stream::unfold(self, |mut follower| async move {
+            let event = follower.next_event().await;
+            Some((event, follower))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::blockdata::constants::genesis_block;
+    use crate::bitcoin::consensus::encode::serialize_hex;
+    use crate::bitcoin::Network;
+    use crate::error::Error;
+    use crate::json::GetBlockHeaderResult;
+    use async_trait::async_trait;
+    use futures::StreamExt;
+    use jsonrpc_async as jsonrpc;
+    use std::sync::Mutex;
+
+    /// A node whose best chain is `chain`, knowing about all blocks in `blocks`.
+    /// Waiting for a new block switches the best chain to `reorg`.
+    struct Node {
+        blocks: Vec<Block>,
+        chain: Mutex<Vec<BlockHash>>,
+        reorg: Vec<BlockHash>,
+    }
+
+    impl Node {
+        fn block(&self, hash: &BlockHash) -> &Block {
+            self.blocks.iter().find(|b| b.block_hash() == *hash).unwrap()
+        }
+
+        fn header(&self, hash: &BlockHash) -> GetBlockHeaderResult {
+            let chain = self.chain.lock().unwrap();
+            let block = self.block(hash);
+            let index = self.blocks.iter().position(|b| b.block_hash() == *hash).unwrap();
+            let (height, confirmations) = match chain.iter().position(|h| h == hash) {
+                Some(height) => (height, (chain.len() - height) as i32),
+                // The blocks are genesis, a1, b1, a2 and b2.
+                None => ([0, 1, 1, 2, 2][index], -1),
+            };
+            GetBlockHeaderResult {
+                hash: *hash,
+                confirmations,
+                height,
+                version: 1,
+                version_hex: Some(vec![0, 0, 0, 1]),
+                merkle_root: block.header.merkle_root,
+                time: 0,
+                median_time: None,
+                nonce: block.header.nonce,
+                bits: String::new(),
+                difficulty: 1.0,
+                chainwork: Vec::new(),
+                n_tx: 1,
+                previous_block_hash: Some(block.header.prev_blockhash),
+                next_block_hash: chain.get(height + 1).filter(|_| confirmations > 0).copied(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl RpcApi for Node {
+        async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            args: &[serde_json::Value],
+        ) -> Result<T> {
+            let hash = || serde_json::from_value::<BlockHash>(args[0].clone()).unwrap();
+            let result = match cmd {
+                "getblockcount" => (self.chain.lock().unwrap().len() - 1).into(),
+                "getblockhash" => {
+                    let height = args[0].as_u64().unwrap() as usize;
+                    serde_json::to_value(self.chain.lock().unwrap()[height])?
+                }
+                "getblock" => serialize_hex(self.block(&hash())).into(),
+                "getblockheader" => serde_json::to_value(self.header(&hash()))?,
+                "waitfornewblock" => {
+                    *self.chain.lock().unwrap() = self.reorg.clone();
+                    serde_json::to_value(BlockRef {
+                        hash: *self.reorg.last().unwrap(),
+                        height: self.reorg.len() as u64 - 1,
+                    })?
+                }
+                _ => {
+                    let err = jsonrpc::error::standard_error(
+                        jsonrpc::error::StandardError::MethodNotFound,
+                        None,
+                    );
+                    return Err(Error::JsonRpc(jsonrpc::Error::Rpc(err)));
+                }
+            };
+            Ok(serde_json::from_value(result)?)
+        }
+    }
+
+    /// The blocks of two chains, genesis <- a1 <- a2 and genesis <- b1 <- b2,
+    /// in the order genesis, a1, b1, a2, b2.
+    fn blocks() -> Vec<Block> {
+        let genesis = genesis_block(Network::Regtest);
+        let mut blocks = vec![genesis.clone()];
+        for i in 1..5 {
+            let mut block = genesis.clone();
+            block.header.prev_blockhash = blocks[i.max(2) - 2].block_hash();
+            block.header.nonce = i as u32;
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    #[tokio::test]
+    async fn test_reorg() {
+        let blocks = blocks();
+        let hashes: Vec<_> = blocks.iter().map(|b| b.block_hash()).collect();
+        let node = Node {
+            chain: Mutex::new(vec![hashes[0], hashes[1], hashes[3]]),
+            reorg: vec![hashes[0], hashes[2], hashes[4]],
+            blocks,
+        };
+
+        let events: Vec<_> =
+            ChainFollower::from_height(&node, 1).into_stream().take(5).collect().await;
+        let events: Vec<_> = events.into_iter().map(|e| e.unwrap()).collect();
+        assert_eq!(
+            events,
+            vec![
+                ChainEvent::BlockConnected(node.blocks[1].clone()),
+                ChainEvent::BlockConnected(node.blocks[3].clone()),
+                ChainEvent::BlockDisconnected(hashes[3]),
+                ChainEvent::BlockDisconnected(hashes[1]),
+                ChainEvent::BlockConnected(node.blocks[2].clone()),
+            ]
+        );
+
+        let mut follower = ChainFollower::from_checkpoint(
+            &node,
+            BlockRef {
+                hash: hashes[2],
+                height: 1,
+            },
+        );
+        assert_eq!(
+            follower.next_event().await.unwrap(),
+            ChainEvent::BlockConnected(node.blocks[4].clone())
+        );
+        assert_eq!(follower.checkpoint().unwrap().height, 2);
+    }
+
+    #[tokio::test]
+    async fn test_start_past_tip() {
+        let blocks = blocks();
+        let hashes: Vec<_> = blocks.iter().map(|b| b.block_hash()).collect();
+        let node = Node {
+            chain: Mutex::new(vec![hashes[0], hashes[1]]),
+            reorg: vec![hashes[0], hashes[1], hashes[3]],
+            blocks,
+        };
+
+        // The follower waits for block 2 instead of failing.
+        let mut follower = ChainFollower::from_height(&node, 2);
+        assert_eq!(
+            follower.next_event().await.unwrap(),
+            ChainEvent::BlockConnected(node.blocks[3].clone())
+        );
+        assert_eq!(follower.checkpoint().unwrap().height, 2);
+    }
+}
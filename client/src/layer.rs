@@ -0,0 +1,360 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Composable layers around the calls of an [RpcApi].
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use log::{log, log_enabled, Level};
+use serde_json::value::RawValue;
+
+use crate::client::{Result, RpcApi};
+use crate::redact::{redact_named, redact_positional};
+
+/// The parameters of an [RpcRequest].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpcParams {
+    Positional(Vec<serde_json::Value>),
+    Named(serde_json::Map<String, serde_json::Value>),
+}
+
+/// A call of an RPC method, as it passes through the layers of a
+/// [LayeredClient].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RpcRequest {
+    pub method: String,
+    pub params: RpcParams,
+}
+
+/// Formats the method and its parameters, with passphrases, private keys
+/// and seeds redacted.
+impl fmt::Display for RpcRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = match self.params {
+            RpcParams::Positional(ref args) => redact_positional(&self.method, args),
+            RpcParams::Named(ref args) => redact_named(&self.method, args),
+        };
+        write!(f, "{} {}", self.method, params)
+    }
+}
+
+/// Something that answers [RpcRequest]s with their raw JSON results, like
+/// a client or a layer around one.
+#[async_trait]
+pub trait RpcService: Send + Sync {
+    /// Send `request` and return its result.
+    async fn call(&self, request: &RpcRequest) -> Result<Box<RawValue>>;
+}
+
+#[async_trait]
+impl<S: RpcService + ?Sized> RpcService for Box<S> {
+    async fn call(&self, request: &RpcRequest) -> Result<Box<RawValue>> {
+        (**self).call(request).await
+    }
+}
+
+#[async_trait]
+impl<S: RpcService + ?Sized> RpcService for Arc<S> {
+    async fn call(&self, request: &RpcRequest) -> Result<Box<RawValue>> {
+        (**self).call(request).await
+    }
+}
+
+/// Wraps an [RpcService] in another one that adds some behavior, like
+/// logging or retries.
+pub trait Layer<S> {
+    /// The service that wraps `S`.
+    type Service: RpcService;
+
+    /// Wrap `inner`.
+    fn layer(&self, inner: S) -> Self::Service;
+}
+
+/// The innermost service of a [LayeredClient], sending requests with an
+/// [RpcApi].
+pub struct ApiService<C> {
+    client: C,
+}
+
+impl<C> ApiService<C> {
+    /// The client that sends the requests.
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl<C: RpcApi + Send + Sync> RpcService for ApiService<C> {
+    async fn call(&self, request: &RpcRequest) -> Result<Box<RawValue>> {
+        match request.params {
+            RpcParams::Positional(ref args) => self.client.call_raw(&request.method, args).await,
+            RpcParams::Named(ref args) => self.client.call_named(&request.method, args).await,
+        }
+    }
+}
+
+/// An [RpcApi] that sends its calls through a stack of [Layer]s.
+///
+/// Layers are added from the inside out, so the last layer added sees the
+/// calls first.
+///
+/// ```no_run
+/// # async fn example(client: bitcoincore_rpc_async::Client) -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::{LayeredClient, LogLayer, RetryLayer, RetryPolicy, RpcApi};
+///
+/// let client = LayeredClient::new(client)
+///     .layer(RetryLayer::new(RetryPolicy::default()))
+///     .layer(LogLayer::new(log::Level::Info));
+/// let count = client.get_block_count().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct LayeredClient<S> {
+    service: S,
+    named_params: bool,
+}
+
+impl<C: RpcApi + Send + Sync> LayeredClient<ApiService<C>> {
+    /// Send the calls that passed the layers with `client`.
+    pub fn new(client: C) -> LayeredClient<ApiService<C>> {
+        let named_params = client.named_params();
+        LayeredClient {
+            service: ApiService {
+                client,
+            },
+            named_params,
+        }
+    }
+}
+
+impl<S: RpcService> LayeredClient<S> {
+    /// Add `layer` around the current layers.
+    pub fn layer<L: Layer<S>>(self, layer: L) -> LayeredClient<L::Service> {
+        LayeredClient {
+            service: layer.layer(self.service),
+            named_params: self.named_params,
+        }
+    }
+
+    /// The outermost service.
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+}
+
+#[async_trait]
+impl<S: RpcService> RpcApi for LayeredClient<S> {
+    async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        let raw = self.call_raw(cmd, args).await?;
+        Ok(serde_json::from_str(raw.get())?)
+    }
+
+    async fn call_raw(&self, cmd: &str, args: &[serde_json::Value]) -> Result<Box<RawValue>> {
+        let request = RpcRequest {
+            method: cmd.to_owned(),
+            params: RpcParams::Positional(args.to_vec()),
+        };
+        self.service.call(&request).await
+    }
+
+    async fn call_named<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        let request = RpcRequest {
+            method: cmd.to_owned(),
+            params: RpcParams::Named(args.clone()),
+        };
+        let raw = self.service.call(&request).await?;
+        Ok(serde_json::from_str(raw.get())?)
+    }
+
+    /// Whether the client inside the layers uses named parameters.
+    fn named_params(&self) -> bool {
+        self.named_params
+    }
+}
+
+/// A [Layer] that logs every call with its duration and outcome.
+#[derive(Clone, Debug)]
+pub struct LogLayer {
+    level: Level,
+}
+
+impl LogLayer {
+    /// Log calls at `level`, with the target `bitcoincore_rpc`.
+    pub fn new(level: Level) -> LogLayer {
+        LogLayer {
+            level,
+        }
+    }
+}
+
+impl<S: RpcService> Layer<S> for LogLayer {
+    type Service = LogService<S>;
+
+    fn layer(&self, inner: S) -> LogService<S> {
+        LogService {
+            inner,
+            level: self.level,
+        }
+    }
+}
+
+/// The service of a [LogLayer].
+pub struct LogService<S> {
+    inner: S,
+    level: Level,
+}
+
+#[async_trait]
+impl<S: RpcService> RpcService for LogService<S> {
+    async fn call(&self, request: &RpcRequest) -> Result<Box<RawValue>> {
+        let start = Instant::now();
+        let result = self.inner.call(request).await;
+        if log_enabled!(target: "bitcoincore_rpc", self.level) {
+            match result {
+                Ok(_) => {
+                    log!(target: "bitcoincore_rpc", self.level, "{} succeeded in {:?}", request, start.elapsed())
+                }
+                Err(ref e) => {
+                    log!(target: "bitcoincore_rpc", self.level, "{} failed after {:?}: {}", request, start.elapsed(), e)
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RetryLayer, RetryPolicy};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Answers `getblockcount` with the number of calls so far.
+    #[derive(Default)]
+    struct Counter {
+        calls: Mutex<u64>,
+    }
+
+    #[async_trait]
+    impl RpcApi for Counter {
+        async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            _: &[serde_json::Value],
+        ) -> Result<T> {
+            let mut calls = self.calls.lock().unwrap();
+            *calls += 1;
+            match cmd {
+                "getblockcount" if *calls > 1 => Ok(serde_json::from_value((*calls).into())?),
+                _ => Err(crate::Error::Timeout),
+            }
+        }
+    }
+
+    /// A layer recording the methods that passed it.
+    #[derive(Clone, Default)]
+    struct Record {
+        methods: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct RecordService<S> {
+        inner: S,
+        methods: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl<S: RpcService> Layer<S> for Record {
+        type Service = RecordService<S>;
+
+        fn layer(&self, inner: S) -> RecordService<S> {
+            RecordService {
+                inner,
+                methods: self.methods.clone(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl<S: RpcService> RpcService for RecordService<S> {
+        async fn call(&self, request: &RpcRequest) -> Result<Box<RawValue>> {
+            self.methods.lock().unwrap().push(request.method.clone());
+            self.inner.call(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layers() {
+        let inner = Record::default();
+        let outer = Record::default();
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let client = LayeredClient::new(Counter::default())
+            .layer(inner.clone())
+            .layer(RetryLayer::new(policy))
+            .layer(LogLayer::new(Level::Debug))
+            .layer(outer.clone());
+
+        // The first call times out and is retried by the retry layer.
+        assert_eq!(client.get_block_count().await.unwrap(), 2);
+        assert_eq!(*outer.methods.lock().unwrap(), ["getblockcount"]);
+        assert_eq!(*inner.methods.lock().unwrap(), ["getblockcount", "getblockcount"]);
+        // Calls that can't be repeated safely are not retried.
+        assert!(client.call::<String>("sendtoaddress", &[]).await.is_err());
+        assert_eq!(inner.methods.lock().unwrap().len(), 3);
+    }
+
+    /// Keeps the messages of all records.
+    struct Capture {
+        messages: Mutex<Vec<String>>,
+    }
+
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.messages.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: Capture = Capture {
+        messages: Mutex::new(Vec::new()),
+    };
+
+    #[tokio::test]
+    async fn test_log_redacts_secrets() {
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+        let client = LayeredClient::new(Counter::default()).layer(LogLayer::new(Level::Info));
+
+        let args = [serde_json::Value::from("hunter2"), serde_json::Value::from(60)];
+        assert!(client.call::<()>("walletpassphrase", &args).await.is_err());
+        let messages = CAPTURE.messages.lock().unwrap();
+        assert!(messages.iter().any(|m| m.starts_with("walletpassphrase [\"[redacted]\",60]")));
+        assert!(messages.iter().all(|m| !m.contains("hunter2")), "{:?}", messages);
+    }
+}
@@ -22,10 +22,76 @@ pub use bitcoincore_rpc_json as json;
 pub use json::bitcoin;
 pub use jsonrpc_async as jsonrpc;
 
+mod batch;
+#[cfg(feature = "blocking")]
+mod blocking;
+mod builder;
+mod cassette;
 mod client;
+mod config;
 mod error;
+mod failover;
+mod follower;
+mod layer;
+mod mempool;
+mod metrics;
+#[cfg(feature = "minimal")]
+mod minimal;
+#[cfg(feature = "mock")]
+mod mock_rpc;
+#[cfg(feature = "mock")]
+mod mock_server;
 mod queryable;
+mod redact;
+mod rest;
+mod retry;
+mod rt;
+#[cfg(feature = "tokio")]
+mod socks;
+#[cfg(feature = "tracing")]
+mod spans;
+#[cfg(feature = "tokio")]
+mod tcp;
+#[cfg(test)]
+mod test_server;
+#[cfg(feature = "tls")]
+mod tls;
+mod transport;
+mod wallet;
+mod zmq;
 
+pub use batch::Batch;
+#[cfg(feature = "blocking")]
+pub use blocking::BlockingClient;
+pub use builder::{ClientBuilder, DEFAULT_TIMEOUT};
+pub use cassette::{RecordingTransport, ReplayTransport};
 pub use client::*;
-pub use error::Error;
+pub use config::{default_rpc_port, network_dir, RpcConfig};
+pub use error::{Error, RpcErrorCode};
+pub use failover::FailoverClient;
+pub use follower::{ChainEvent, ChainFollower};
+pub use layer::{
+    ApiService, Layer, LayeredClient, LogLayer, LogService, RpcParams, RpcRequest, RpcService,
+};
+pub use mempool::{MempoolEvent, MempoolWatcher, RemovalReason};
+pub use metrics::{CallOutcome, Metrics, PrometheusMetrics};
+#[cfg(feature = "minimal")]
+pub use minimal::MinimalTransport;
+#[cfg(feature = "mock")]
+pub use mock_rpc::{ExpectedCall, MockRpc};
+#[cfg(feature = "mock")]
+pub use mock_server::MockServer;
 pub use queryable::*;
+pub use rest::{GetUtxosResult, RestClient, Utxo};
+pub use retry::{RetryClient, RetryLayer, RetryPolicy, RetryService};
+#[cfg(feature = "tokio")]
+pub use socks::Socks5Proxy;
+#[cfg(feature = "tokio")]
+pub use tcp::TcpTransport;
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+pub use transport::{PoolStats, Transport, DEFAULT_MAX_CONNECTIONS, DEFAULT_MAX_RESPONSE_SIZE};
+pub use wallet::WalletClient;
+pub use zmq::{
+    GetZmqNotificationsResult, SequenceEvent, ZmqMessage, ZmqNotification, ZmqSubscriber, ZmqTopic,
+};
@@ -0,0 +1,398 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Watching the mempool for added and removed transactions.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use futures::stream::{self, Stream};
+use log::debug;
+use serde::Deserialize;
+
+use crate::bitcoin::{BlockHash, Txid};
+use crate::client::{Result, RpcApi};
+use crate::error::RpcErrorCode;
+use crate::json::GetMempoolEntryResult;
+use crate::rt;
+
+/// The maximum number of new blocks searched for removed transactions.
+const MAX_BLOCKS_SEARCHED: usize = 10;
+
+/// Why a transaction left the mempool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The transaction was included in this block.
+    Confirmed(BlockHash),
+    /// The transaction was not found in a new block. It was evicted,
+    /// replaced, expired or conflicted with a block, or it was confirmed
+    /// too many blocks ago to tell.
+    Other,
+}
+
+/// A change of the mempool reported by a [MempoolWatcher].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MempoolEvent {
+    /// The transaction entered the mempool.
+    TxAdded(Txid, GetMempoolEntryResult),
+    /// The transaction left the mempool.
+    TxRemoved(Txid, RemovalReason),
+}
+
+/// The result of "getrawmempool" with `mempool_sequence`.
+#[derive(Deserialize)]
+struct RawMempoolSequence {
+    txids: Vec<Txid>,
+    mempool_sequence: u64,
+}
+
+/// Reports transactions entering and leaving the mempool of a node by
+/// polling `getrawmempool`.
+///
+/// Only the txids of the mempool are kept in memory. The entry of an added
+/// transaction is fetched with [RpcApi::get_mempool_entry] when its event is
+/// returned; transactions that left the mempool again by then are skipped.
+/// The transactions in the mempool when the watcher starts are reported as
+/// added.
+///
+/// ```no_run
+/// # async fn example(client: &bitcoincore_rpc_async::Client) -> bitcoincore_rpc_async::Result<()> {
+/// use std::time::Duration;
+/// use bitcoincore_rpc_async::{MempoolEvent, MempoolWatcher};
+///
+/// let mut watcher = MempoolWatcher::new(client).interval(Duration::from_secs(5));
+/// loop {
+///     match watcher.next_event().await? {
+///         MempoolEvent::TxAdded(txid, entry) => println!("added {} ({} vB)", txid, entry.vsize),
+///         MempoolEvent::TxRemoved(txid, reason) => println!("removed {}: {:?}", txid, reason),
+///     }
+/// }
+/// # }
+/// ```
+pub struct MempoolWatcher<'a, C> {
+    client: &'a C,
+    interval: Duration,
+    max_txs: usize,
+    use_sequence: bool,
+    /// The txids in the mempool at the last poll, including those whose
+    /// events are still pending.
+    known: HashSet<Txid>,
+    /// Removed transactions are ready, added ones still need their entry.
+    pending: VecDeque<(Txid, Option<RemovalReason>)>,
+    last_poll: Option<Instant>,
+    sequence: Option<u64>,
+    tip: Option<BlockHash>,
+}
+
+impl<'a, C: RpcApi + Sync> MempoolWatcher<'a, C> {
+    /// Watch the mempool of the node of `client`.
+    pub fn new(client: &'a C) -> MempoolWatcher<'a, C> {
+        MempoolWatcher {
+            client,
+            interval: Duration::from_secs(1),
+            max_txs: 500_000,
+            use_sequence: false,
+            known: HashSet::new(),
+            pending: VecDeque::new(),
+            last_poll: None,
+            sequence: None,
+            tip: None,
+        }
+    }
+
+    /// Set the time between two polls of the mempool, 1 second if not set.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the maximum number of transactions to keep track of, 500,000 if
+    /// not set. This includes the removed transactions whose events are
+    /// still pending. New transactions are ignored while the limit is
+    /// reached.
+    pub fn max_txs(mut self, max_txs: usize) -> Self {
+        self.max_txs = max_txs;
+        self
+    }
+
+    /// Use the `mempool_sequence` of `getrawmempool` to skip comparing the
+    /// mempool when it didn't change. Needs Bitcoin Core 0.21 or later.
+    pub fn mempool_sequence(mut self, use_sequence: bool) -> Self {
+        self.use_sequence = use_sequence;
+        self
+    }
+
+    /// Wait for the next change of the mempool.
+    ///
+    /// If this returns [Err], the watcher keeps its state, so calling it
+    /// again retries.
+    pub async fn next_event(&mut self) -> Result<MempoolEvent> {
+        loop {
+            match self.pending.front().copied() {
+                Some((txid, Some(reason))) => {
+                    self.pending.pop_front();
+                    return Ok(MempoolEvent::TxRemoved(txid, reason));
+                }
+                Some((txid, None)) => {
+                    let entry = self.client.get_mempool_entry(&txid).await;
+                    self.pending.pop_front();
+                    match entry {
+                        Ok(entry) => return Ok(MempoolEvent::TxAdded(txid, entry)),
+                        // The transaction already left the mempool again.
+                        Err(ref e) if e.rpc_code() == Some(RpcErrorCode::InvalidAddressOrKey) => {
+                            self.known.remove(&txid);
+                        }
+                        Err(e) => {
+                            self.pending.push_front((txid, None));
+                            return Err(e);
+                        }
+                    }
+                }
+                None => self.poll().await?,
+            }
+        }
+    }
+
+    /// Wait for the interval and compare the mempool to the last poll.
+    async fn poll(&mut self) -> Result<()> {
+        if let Some(last_poll) = self.last_poll {
+            rt::sleep_until(last_poll + self.interval).await;
+        }
+        self.last_poll = Some(Instant::now());
+
+        let (txids, sequence) = if self.use_sequence {
+            let mempool: RawMempoolSequence =
+                self.client.call("getrawmempool", &[false.into(), true.into()]).await?;
+            if self.sequence == Some(mempool.mempool_sequence) {
+                return Ok(());
+            }
+            (mempool.txids, Some(mempool.mempool_sequence))
+        } else {
+            (self.client.get_raw_mempool().await?, None)
+        };
+        // Transactions can only be found in blocks that were mined before
+        // the mempool was polled, so get the tip afterwards.
+        let tip = self.client.get_best_block_hash().await?;
+
+        let current: HashSet<_> = txids.iter().copied().collect();
+        let removed: Vec<_> =
+            self.known.iter().filter(|t| !current.contains(*t)).copied().collect();
+        if !removed.is_empty() {
+            let confirmed = self.confirmed_since(tip).await?;
+            for txid in removed {
+                self.known.remove(&txid);
+                let reason = match confirmed.get(&txid) {
+                    Some(block) => RemovalReason::Confirmed(*block),
+                    None => RemovalReason::Other,
+                };
+                self.pending.push_back((txid, Some(reason)));
+            }
+        }
+        self.tip = Some(tip);
+        self.sequence = sequence;
+
+        let removals = self.pending.iter().filter(|(_, reason)| reason.is_some()).count();
+        for txid in txids {
+            if self.known.contains(&txid) {
+                continue;
+            }
+            if self.known.len() + removals >= self.max_txs {
+                debug!(target: "bitcoincore_rpc", "mempool watcher ignores new transactions, limit of {} reached", self.max_txs);
+                break;
+            }
+            self.known.insert(txid);
+            self.pending.push_back((txid, None));
+        }
+        Ok(())
+    }
+
+    /// The transactions in the blocks from `tip` back to the last tip.
+    async fn confirmed_since(&self, tip: BlockHash) -> Result<HashMap<Txid, BlockHash>> {
+        let mut confirmed = HashMap::new();
+        let mut hash = tip;
+        for _ in 0..MAX_BLOCKS_SEARCHED {
+            if self.tip == Some(hash) || self.tip.is_none() {
+                break;
+            }
+            let block = self.client.get_block(&hash).await?;
+            confirmed.extend(block.txdata.iter().map(|tx| (tx.txid(), hash)));
+            hash = block.header.prev_blockhash;
+        }
+        Ok(confirmed)
+    }
+
+    /// Turn the watcher into an endless [Stream] of its events.
+    ///
+    /// Errors don't end the stream; polling it again after an error retries.
+    pub fn into_stream(self) -> impl Stream<Item = Result<MempoolEvent>> + 'a {
+        stream::unfold(self, |mut watcher| async move {
+            let event = watcher.next_event().await;
+            Some((event, watcher))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::blockdata::constants::genesis_block;
+    use crate::bitcoin::consensus::encode::serialize_hex;
+    use crate::bitcoin::hashes::Hash;
+    use crate::bitcoin::{Amount, Block, Network, PackedLockTime, Transaction};
+    use crate::error::Error;
+    use crate::json::GetMempoolEntryResultFees;
+    use async_trait::async_trait;
+    use jsonrpc_async as jsonrpc;
+    use std::sync::Mutex;
+
+    /// A node that moves on to the next `(tip, mempool)` state every time its
+    /// mempool is polled.
+    struct Node {
+        blocks: Vec<Block>,
+        states: Mutex<VecDeque<(usize, Vec<Txid>)>>,
+        tip: Mutex<usize>,
+    }
+
+    fn entry() -> GetMempoolEntryResult {
+        GetMempoolEntryResult {
+            vsize: 100,
+            weight: None,
+            time: 0,
+            height: 0,
+            descendant_count: 1,
+            descendant_size: 100,
+            ancestor_count: 1,
+            ancestor_size: 100,
+            wtxid: Txid::all_zeros(),
+            fees: GetMempoolEntryResultFees {
+                base: Amount::ZERO,
+                modified: Amount::ZERO,
+                ancestor: Amount::ZERO,
+                descendant: Amount::ZERO,
+            },
+            depends: vec![],
+            spent_by: vec![],
+            bip125_replaceable: false,
+            unbroadcast: None,
+        }
+    }
+
+    #[async_trait]
+    impl RpcApi for Node {
+        async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            args: &[serde_json::Value],
+        ) -> Result<T> {
+            let result = match cmd {
+                "getrawmempool" => {
+                    let (tip, txids) = self.states.lock().unwrap().pop_front().unwrap();
+                    *self.tip.lock().unwrap() = tip;
+                    serde_json::to_value(txids)?
+                }
+                "getbestblockhash" => {
+                    serde_json::to_value(self.blocks[*self.tip.lock().unwrap()].block_hash())?
+                }
+                "getblock" => {
+                    let hash: BlockHash = serde_json::from_value(args[0].clone())?;
+                    serialize_hex(self.blocks.iter().find(|b| b.block_hash() == hash).unwrap())
+                        .into()
+                }
+                "getmempoolentry" => serde_json::to_value(entry())?,
+                _ => {
+                    let err = jsonrpc::error::standard_error(
+                        jsonrpc::error::StandardError::MethodNotFound,
+                        None,
+                    );
+                    return Err(Error::JsonRpc(jsonrpc::Error::Rpc(err)));
+                }
+            };
+            Ok(serde_json::from_value(result)?)
+        }
+    }
+
+    /// Three transactions that differ in their lock time.
+    fn txs() -> Vec<Transaction> {
+        let genesis = genesis_block(Network::Regtest);
+        (1..4)
+            .map(|i| Transaction {
+                lock_time: PackedLockTime(i),
+                ..genesis.txdata[0].clone()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_mempool_watcher() {
+        let genesis = genesis_block(Network::Regtest);
+        let txs = txs();
+        let txids: Vec<_> = txs.iter().map(|tx| tx.txid()).collect();
+        let mut block = genesis.clone();
+        block.header.prev_blockhash = genesis.block_hash();
+        block.txdata = vec![txs[0].clone()];
+        let block_hash = block.block_hash();
+
+        // tx 1 is confirmed and tx 2 is evicted while tx 3 arrives.
+        let node = Node {
+            blocks: vec![genesis, block],
+            states: Mutex::new(VecDeque::from([
+                (0, vec![txids[0], txids[1]]),
+                (1, vec![txids[2]]),
+            ])),
+            tip: Mutex::new(0),
+        };
+        let mut watcher = MempoolWatcher::new(&node).interval(Duration::ZERO);
+        let mut events = Vec::new();
+        for _ in 0..5 {
+            events.push(watcher.next_event().await.unwrap());
+        }
+        let mut removed = [
+            MempoolEvent::TxRemoved(txids[0], RemovalReason::Confirmed(block_hash)),
+            MempoolEvent::TxRemoved(txids[1], RemovalReason::Other),
+        ];
+        // Removals are reported in no particular order.
+        if events[2] != removed[0] {
+            removed.swap(0, 1);
+        }
+        assert_eq!(
+            events,
+            vec![
+                MempoolEvent::TxAdded(txids[0], entry()),
+                MempoolEvent::TxAdded(txids[1], entry()),
+                removed[0].clone(),
+                removed[1].clone(),
+                MempoolEvent::TxAdded(txids[2], entry()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_txs() {
+        let txids: Vec<_> = txs().iter().map(|tx| tx.txid()).collect();
+        let node = Node {
+            blocks: vec![genesis_block(Network::Regtest)],
+            states: Mutex::new(VecDeque::from([
+                (0, vec![txids[0], txids[1]]),
+                (0, vec![txids[2]]),
+                (0, vec![txids[2]]),
+            ])),
+            tip: Mutex::new(0),
+        };
+        let mut watcher = MempoolWatcher::new(&node).interval(Duration::ZERO).max_txs(2);
+        let mut events = Vec::new();
+        for _ in 0..5 {
+            events.push(watcher.next_event().await.unwrap());
+        }
+        // The pending removals count towards the limit, so tx 3 is only
+        // added by the third poll.
+        assert_eq!(events[4], MempoolEvent::TxAdded(txids[2], entry()));
+        assert!(node.states.lock().unwrap().is_empty());
+    }
+}
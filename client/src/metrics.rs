@@ -0,0 +1,343 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Collecting metrics of the calls of a [crate::Client].
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+
+/// The upper bounds of the buckets of the latency histograms of
+/// [PrometheusMetrics], in seconds.
+const LATENCY_BUCKETS: [f64; 13] =
+    [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// How a call ended, as reported to [Metrics::call_finished].
+#[derive(Clone, Copy, Debug)]
+pub enum CallOutcome<'a> {
+    Success,
+    /// The call failed, including with an error returned by the node.
+    Error(&'a Error),
+    /// The call was dropped before it finished.
+    Cancelled,
+}
+
+/// Receives metrics of the calls of a [crate::Client], set with
+/// [crate::ClientBuilder::metrics].
+///
+/// All methods do nothing by default. They are called on the path of every
+/// call, so they should be cheap.
+pub trait Metrics: Send + Sync {
+    /// A call of `method` was started.
+    fn call_started(&self, method: &str) {
+        let _ = method;
+    }
+
+    /// A call of `method` ended after `latency`.
+    ///
+    /// Called once for every [Metrics::call_started].
+    fn call_finished(&self, method: &str, latency: Duration, outcome: CallOutcome<'_>) {
+        let _ = (method, latency, outcome);
+    }
+
+    /// An HTTP request with a body of `sent` bytes was answered with a body
+    /// of `received` bytes.
+    fn bytes_transferred(&self, sent: usize, received: usize) {
+        let _ = (sent, received);
+    }
+}
+
+impl fmt::Debug for dyn Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Metrics")
+    }
+}
+
+/// Reports a call to [Metrics] when it is started, and as cancelled when it
+/// is dropped before [CallGuard::finish].
+pub(crate) struct CallGuard<'a> {
+    metrics: &'a dyn Metrics,
+    method: &'a str,
+    start: Instant,
+    finished: bool,
+}
+
+impl<'a> CallGuard<'a> {
+    pub(crate) fn start(metrics: &'a dyn Metrics, method: &'a str) -> CallGuard<'a> {
+        metrics.call_started(method);
+        CallGuard {
+            metrics,
+            method,
+            start: Instant::now(),
+            finished: false,
+        }
+    }
+
+    pub(crate) fn finish(mut self, outcome: CallOutcome<'_>) {
+        self.finished = true;
+        self.metrics.call_finished(self.method, self.start.elapsed(), outcome);
+    }
+}
+
+impl Drop for CallGuard<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.metrics.call_finished(self.method, self.start.elapsed(), CallOutcome::Cancelled);
+        }
+    }
+}
+
+/// The label of the error code of `err` in [PrometheusMetrics].
+fn error_code_label(err: &Error) -> String {
+    match (err.rpc_code(), err) {
+        (Some(code), _) => code.code().to_string(),
+        (None, &Error::Timeout) => "timeout".to_owned(),
+        (None, _) => "other".to_owned(),
+    }
+}
+
+/// The latency histogram of a method.
+#[derive(Default)]
+struct Histogram {
+    /// The number of calls per bucket of [LATENCY_BUCKETS], not cumulative.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|&b| seconds <= b) {
+            self.buckets[i] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct State {
+    calls: BTreeMap<String, u64>,
+    errors: BTreeMap<(String, String), u64>,
+    cancelled: BTreeMap<String, u64>,
+    in_flight: BTreeMap<String, i64>,
+    latency: BTreeMap<String, Histogram>,
+    sent_bytes: u64,
+    received_bytes: u64,
+}
+
+/// [Metrics] that are kept in memory and exported in the Prometheus text
+/// format.
+///
+/// ```no_run
+/// # async fn example() -> bitcoincore_rpc_async::Result<()> {
+/// use std::sync::Arc;
+/// use bitcoincore_rpc_async::{Auth, ClientBuilder, PrometheusMetrics, RpcApi};
+///
+/// let metrics = Arc::new(PrometheusMetrics::new());
+/// let client = ClientBuilder::new("http://localhost:8332".into(), Auth::None)
+///     .metrics(metrics.clone())
+///     .build()
+///     .await?;
+/// client.get_block_count().await?;
+/// println!("{}", metrics.render());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct PrometheusMetrics {
+    state: Mutex<State>,
+}
+
+impl PrometheusMetrics {
+    pub fn new() -> PrometheusMetrics {
+        PrometheusMetrics::default()
+    }
+
+    /// Export the metrics in the Prometheus text format, to be served to a
+    /// Prometheus server.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let mut out = String::new();
+        // Writing to a String can't fail.
+        let _ = render(&state, &mut out);
+        out
+    }
+}
+
+/// A label value, escaped as the Prometheus text format requires.
+struct Label<'a>(&'a str);
+
+impl fmt::Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn render(state: &State, out: &mut String) -> fmt::Result {
+    writeln!(out, "# HELP bitcoincore_rpc_calls_total Calls started per method.")?;
+    writeln!(out, "# TYPE bitcoincore_rpc_calls_total counter")?;
+    for (method, count) in &state.calls {
+        writeln!(out, "bitcoincore_rpc_calls_total{{method=\"{}\"}} {}", Label(method), count)?;
+    }
+    writeln!(out, "# HELP bitcoincore_rpc_errors_total Failed calls per method and error code.")?;
+    writeln!(out, "# TYPE bitcoincore_rpc_errors_total counter")?;
+    for ((method, code), count) in &state.errors {
+        writeln!(
+            out,
+            "bitcoincore_rpc_errors_total{{method=\"{}\",code=\"{}\"}} {}",
+            Label(method),
+            Label(code),
+            count
+        )?;
+    }
+    writeln!(out, "# HELP bitcoincore_rpc_cancelled_total Calls dropped before they finished.")?;
+    writeln!(out, "# TYPE bitcoincore_rpc_cancelled_total counter")?;
+    for (method, count) in &state.cancelled {
+        writeln!(out, "bitcoincore_rpc_cancelled_total{{method=\"{}\"}} {}", Label(method), count)?;
+    }
+    writeln!(out, "# HELP bitcoincore_rpc_in_flight Calls in progress per method.")?;
+    writeln!(out, "# TYPE bitcoincore_rpc_in_flight gauge")?;
+    for (method, count) in &state.in_flight {
+        writeln!(out, "bitcoincore_rpc_in_flight{{method=\"{}\"}} {}", Label(method), count)?;
+    }
+    writeln!(out, "# HELP bitcoincore_rpc_latency_seconds Latency of finished calls per method.")?;
+    writeln!(out, "# TYPE bitcoincore_rpc_latency_seconds histogram")?;
+    for (method, histogram) in &state.latency {
+        let method = Label(method);
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+            cumulative += count;
+            writeln!(
+                out,
+                "bitcoincore_rpc_latency_seconds_bucket{{method=\"{}\",le=\"{}\"}} {}",
+                method, bound, cumulative
+            )?;
+        }
+        writeln!(
+            out,
+            "bitcoincore_rpc_latency_seconds_bucket{{method=\"{}\",le=\"+Inf\"}} {}",
+            method, histogram.count
+        )?;
+        writeln!(
+            out,
+            "bitcoincore_rpc_latency_seconds_sum{{method=\"{}\"}} {}",
+            method, histogram.sum
+        )?;
+        writeln!(
+            out,
+            "bitcoincore_rpc_latency_seconds_count{{method=\"{}\"}} {}",
+            method, histogram.count
+        )?;
+    }
+    writeln!(out, "# HELP bitcoincore_rpc_sent_bytes_total Bytes of request bodies sent.")?;
+    writeln!(out, "# TYPE bitcoincore_rpc_sent_bytes_total counter")?;
+    writeln!(out, "bitcoincore_rpc_sent_bytes_total {}", state.sent_bytes)?;
+    writeln!(
+        out,
+        "# HELP bitcoincore_rpc_received_bytes_total Bytes of response bodies received."
+    )?;
+    writeln!(out, "# TYPE bitcoincore_rpc_received_bytes_total counter")?;
+    writeln!(out, "bitcoincore_rpc_received_bytes_total {}", state.received_bytes)
+}
+
+impl Metrics for PrometheusMetrics {
+    fn call_started(&self, method: &str) {
+        let mut state = self.state.lock().unwrap();
+        *state.calls.entry(method.to_owned()).or_default() += 1;
+        *state.in_flight.entry(method.to_owned()).or_default() += 1;
+    }
+
+    fn call_finished(&self, method: &str, latency: Duration, outcome: CallOutcome<'_>) {
+        let mut state = self.state.lock().unwrap();
+        *state.in_flight.entry(method.to_owned()).or_default() -= 1;
+        match outcome {
+            CallOutcome::Success => {}
+            CallOutcome::Error(err) => {
+                let key = (method.to_owned(), error_code_label(err));
+                *state.errors.entry(key).or_default() += 1;
+            }
+            CallOutcome::Cancelled => {
+                *state.cancelled.entry(method.to_owned()).or_default() += 1;
+                return;
+            }
+        }
+        state.latency.entry(method.to_owned()).or_default().observe(latency.as_secs_f64());
+    }
+
+    fn bytes_transferred(&self, sent: usize, received: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.sent_bytes += sent as u64;
+        state.received_bytes += received as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Auth, RpcApi};
+    use crate::test_server::serve;
+    use crate::ClientBuilder;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_prometheus_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut bodies = vec![
+            r#"{"result":42,"error":null,"id":0}"#,
+            r#"{"result":null,"error":{"code":-28,"message":"Loading"},"id":1}"#,
+        ]
+        .into_iter();
+        let server = tokio::spawn(serve(listener, 2, move |_| (200, bodies.next().unwrap())));
+
+        let metrics = Arc::new(PrometheusMetrics::new());
+        let client = ClientBuilder::new(format!("http://{}", addr), Auth::None)
+            .metrics(metrics.clone())
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+        assert!(client.get_block_count().await.unwrap_err().is_warmup());
+        server.await.unwrap();
+
+        let text = metrics.render();
+        assert!(text.contains("bitcoincore_rpc_calls_total{method=\"getblockcount\"} 2\n"));
+        assert!(text
+            .contains("bitcoincore_rpc_errors_total{method=\"getblockcount\",code=\"-28\"} 1\n"));
+        assert!(text.contains("bitcoincore_rpc_in_flight{method=\"getblockcount\"} 0\n"));
+        assert!(
+            text.contains("bitcoincore_rpc_latency_seconds_count{method=\"getblockcount\"} 2\n")
+        );
+        assert!(!text.contains("bitcoincore_rpc_sent_bytes_total 0\n"));
+
+        // Label values are escaped.
+        let metrics = PrometheusMetrics::new();
+        metrics.call_started("a\\b\"c\nd");
+        let text = metrics.render();
+        assert!(
+            text.contains("bitcoincore_rpc_calls_total{method=\"a\\\\b\\\"c\\nd\"} 1\n"),
+            "{}",
+            text
+        );
+    }
+}
@@ -0,0 +1,212 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! A minimal HTTP transport on async-io, with the `minimal` feature.
+
+use std::{io, net, str};
+
+use async_net::TcpStream;
+use async_trait::async_trait;
+use futures::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::transport::{
+    invalid_response, response_too_large, Transport, DEFAULT_MAX_RESPONSE_SIZE,
+};
+
+/// A [Transport] that sends every request on a new TCP connection with
+/// HTTP/1.1 and `Connection: close`.
+///
+/// It runs on the reactor of async-io, so it works with any executor and
+/// doesn't need tokio. It is the built-in transport when the `tokio` feature
+/// is disabled, and can be passed to [crate::ClientBuilder::transport]
+/// otherwise.
+#[derive(Debug)]
+pub struct MinimalTransport {
+    addr: net::SocketAddr,
+    /// The value of the `Host` HTTP header.
+    host: String,
+    max_response_size: usize,
+}
+
+impl MinimalTransport {
+    /// Send requests to `addr`, with `host` as the `Host` HTTP header.
+    pub fn new(addr: net::SocketAddr, host: String) -> MinimalTransport {
+        MinimalTransport {
+            addr,
+            host,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+        }
+    }
+
+    /// Fail requests whose response, including its headers, is larger than
+    /// `max` bytes. [DEFAULT_MAX_RESPONSE_SIZE] if not set.
+    pub fn max_response_size(mut self, max: usize) -> MinimalTransport {
+        self.max_response_size = max;
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for MinimalTransport {
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        auth: Option<&str>,
+        body: &[u8],
+    ) -> crate::Result<(u16, Vec<u8>)> {
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n",
+            method,
+            path,
+            self.host,
+            body.len(),
+        );
+        if let Some(auth) = auth {
+            head.push_str("Authorization: ");
+            head.push_str(auth);
+            head.push_str("\r\n");
+        }
+        head.push_str("\r\n");
+        let mut request = head.into_bytes();
+        request.extend_from_slice(body);
+
+        let mut sock = TcpStream::connect(self.addr).await?;
+        sock.write_all(&request).await?;
+        let mut response = Vec::new();
+        let limit = self.max_response_size as u64 + 1;
+        (&mut sock).take(limit).read_to_end(&mut response).await?;
+        if response.len() > self.max_response_size {
+            return Err(response_too_large(self.max_response_size).into());
+        }
+        Ok(parse_response(&response)?)
+    }
+}
+
+/// Split `buf` at the first CRLF, returning the line without it and the rest.
+fn split_line(buf: &[u8]) -> io::Result<(&[u8], &[u8])> {
+    match buf.windows(2).position(|w| w == b"\r\n") {
+        Some(end) => Ok((&buf[..end], &buf[end + 2..])),
+        None => Err(invalid_response()),
+    }
+}
+
+/// Parse a whole HTTP response into its status code and body.
+fn parse_response(mut response: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let (line, rest) = split_line(response)?;
+    let line = str::from_utf8(line).map_err(|_| invalid_response())?;
+    if !line.starts_with("HTTP/1.") {
+        return Err(invalid_response());
+    }
+    let response_code: u16 =
+        line.get(9..12).ok_or_else(invalid_response)?.parse().map_err(|_| invalid_response())?;
+    response = rest;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        let (line, rest) = split_line(response)?;
+        response = rest;
+        if line.is_empty() {
+            break;
+        }
+        let line = str::from_utf8(line).map_err(|_| invalid_response())?;
+        if let Some((name, value)) = line.split_once(':') {
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.parse().map_err(|_| invalid_response())?);
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            }
+        }
+    }
+
+    if response_code == 401 {
+        // There is no body in a 401 response.
+        return Ok((response_code, Vec::new()));
+    }
+    let body = if chunked {
+        decode_chunked(response)?
+    } else if let Some(len) = content_length {
+        response.get(..len).ok_or(io::ErrorKind::UnexpectedEof)?.to_vec()
+    } else {
+        response.to_vec()
+    };
+    Ok((response_code, body))
+}
+
+/// Decode a body sent with `Transfer-Encoding: chunked`.
+fn decode_chunked(mut chunks: &[u8]) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let (line, rest) = split_line(chunks)?;
+        let line = str::from_utf8(line).map_err(|_| invalid_response())?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| invalid_response())?;
+        if size == 0 {
+            return Ok(body);
+        }
+        body.extend_from_slice(rest.get(..size).ok_or(io::ErrorKind::UnexpectedEof)?);
+        // Skip the CRLF after the chunk data.
+        chunks = rest.get(size + 2..).ok_or(io::ErrorKind::UnexpectedEof)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Auth, RpcApi};
+    use crate::test_server::serve;
+    use crate::ClientBuilder;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_response() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n42";
+        assert_eq!(parse_response(response).unwrap(), (200, b"42".to_vec()));
+        let response = b"HTTP/1.1 500 -\r\nTransfer-Encoding: chunked\r\n\r\n2\r\n{}\r\n0\r\n\r\n";
+        assert_eq!(parse_response(response).unwrap(), (500, b"{}".to_vec()));
+        let response = b"HTTP/1.0 404 Not Found\r\n\r\nnot found";
+        assert_eq!(parse_response(response).unwrap(), (404, b"not found".to_vec()));
+        assert!(parse_response(b"HTTP/1.1 2\r\n\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\n42").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_minimal_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve(listener, 3, |req| {
+            assert_eq!(req.header("Connection"), Some("close"));
+            (200, format!(r#"{{"result":"{}","error":null,"id":1}}"#, req.path))
+        }));
+
+        let transport = MinimalTransport::new(addr, addr.to_string());
+        let client = ClientBuilder::new(format!("http://{}", addr), Auth::None)
+            .transport(Arc::new(transport))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.call::<String>("getblockcount", &[]).await.unwrap(), "/");
+        let path: String = client.wallet("w").call("getbalance", &[]).await.unwrap();
+        assert_eq!(path, "/wallet/w");
+
+        let transport = MinimalTransport::new(addr, addr.to_string()).max_response_size(64);
+        let client = ClientBuilder::new(format!("http://{}", addr), Auth::None)
+            .transport(Arc::new(transport))
+            .build()
+            .await
+            .unwrap();
+        assert!(client.call::<String>("getblockcount", &[]).await.is_err());
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,363 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! A programmable [RpcApi] for unit tests.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use jsonrpc_async as jsonrpc;
+use serde_json::Value;
+
+use crate::client::{Result, RpcApi};
+use crate::error::Error;
+
+/// What a call that matches an expectation returns.
+enum Response {
+    Value(Value),
+    RpcError(jsonrpc::error::RpcError),
+    Error(Box<dyn Fn() -> Error + Send + Sync>),
+}
+
+/// How many calls an expectation expects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Times {
+    Exactly(usize),
+    Any,
+}
+
+struct Expectation {
+    cmd: String,
+    /// The arguments to match, any if [None].
+    args: Option<Vec<Value>>,
+    response: Response,
+    times: Times,
+    calls: usize,
+}
+
+impl Expectation {
+    fn matches(&self, cmd: &str, args: &[Value]) -> bool {
+        let args_match = match self.args {
+            Some(ref expected) => expected[..] == *args,
+            None => true,
+        };
+        self.cmd == cmd && args_match
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.times == Times::Exactly(self.calls)
+    }
+
+    fn is_satisfied(&self) -> bool {
+        match self.times {
+            Times::Exactly(times) => self.calls == times,
+            Times::Any => true,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let args = match self.args {
+            Some(ref args) => Value::from(args.clone()).to_string(),
+            None => "<any>".to_owned(),
+        };
+        let times = match self.times {
+            Times::Exactly(times) => times.to_string(),
+            Times::Any => "any".to_owned(),
+        };
+        format!("{} {} (called {} of {} times)", self.cmd, args, self.calls, times)
+    }
+}
+
+/// An [RpcApi] that answers calls from a list of expected calls, so any
+/// method of [RpcApi] can be tested without a node.
+///
+/// Every expected call has a canned JSON result or error and is expected
+/// exactly once unless set otherwise. A call that matches no expectation
+/// panics with the list of expectations. [MockRpc::verify] panics if an
+/// expected call was not made. Calls with named arguments are matched and
+/// recorded with the arguments as a single JSON object.
+///
+/// ```
+/// # #[tokio::main] async fn main() {
+/// use bitcoincore_rpc_async::{MockRpc, RpcApi};
+/// use serde_json::json;
+///
+/// let mock = MockRpc::new();
+/// mock.expect("getblockcount").returns(json!(100));
+/// mock.expect("getblockhash").with_args(&[json!(100)]).returns_rpc_error(-8, "Block height out of range");
+///
+/// assert_eq!(mock.get_block_count().await.unwrap(), 100);
+/// assert!(mock.get_block_hash(100).await.is_err());
+/// mock.verify();
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MockRpc {
+    expectations: Mutex<Vec<Expectation>>,
+    /// Whether calls have to be made in the order of the expectations.
+    ordered: bool,
+    /// The index of the expectation that matched the last call, in order.
+    cursor: AtomicUsize,
+    /// Whether [RpcApi::named_params] is enabled.
+    named_params: bool,
+    calls: Mutex<Vec<(String, Vec<Value>)>>,
+}
+
+/// An expected call registered with [MockRpc::expect], returning `null`
+/// exactly once unless set otherwise.
+pub struct ExpectedCall<'a> {
+    mock: &'a MockRpc,
+    index: usize,
+}
+
+impl<'a> ExpectedCall<'a> {
+    fn update(self, f: impl FnOnce(&mut Expectation)) -> Self {
+        f(&mut self.mock.expectations.lock().unwrap()[self.index]);
+        self
+    }
+
+    /// Only match calls with exactly these arguments.
+    pub fn with_args(self, args: &[Value]) -> Self {
+        self.update(|e| e.args = Some(args.to_vec()))
+    }
+
+    /// Expect `times` calls.
+    pub fn times(self, times: usize) -> Self {
+        self.update(|e| e.times = Times::Exactly(times))
+    }
+
+    /// Expect any number of calls, including none.
+    pub fn repeatedly(self) -> Self {
+        self.update(|e| e.times = Times::Any)
+    }
+
+    /// Return `result` as the JSON result of the call.
+    pub fn returns(self, result: Value) -> Self {
+        self.update(|e| e.response = Response::Value(result))
+    }
+
+    /// Return a JSON-RPC error, like bitcoind does for a failed call.
+    pub fn returns_rpc_error(self, code: i32, message: &str) -> Self {
+        let err = jsonrpc::error::RpcError {
+            code,
+            message: message.to_owned(),
+            data: None,
+        };
+        self.update(|e| e.response = Response::RpcError(err))
+    }
+
+    /// Return the error made by `error`, for example a transport error.
+    pub fn returns_error(self, error: impl Fn() -> Error + Send + Sync + 'static) -> Self {
+        self.update(|e| e.response = Response::Error(Box::new(error)))
+    }
+}
+
+impl MockRpc {
+    /// Create a mock that accepts the expected calls in any order.
+    pub fn new() -> MockRpc {
+        MockRpc::default()
+    }
+
+    /// Create a mock that accepts the expected calls only in the order in
+    /// which they were registered.
+    ///
+    /// Expected calls set to [ExpectedCall::repeatedly] may be skipped, but
+    /// not called again once a later expected call was made.
+    pub fn in_order() -> MockRpc {
+        MockRpc {
+            ordered: true,
+            ..Default::default()
+        }
+    }
+
+    /// Make the methods of [RpcApi] call with named arguments, see
+    /// [RpcApi::named_params].
+    pub fn named_params(mut self, named_params: bool) -> MockRpc {
+        self.named_params = named_params;
+        self
+    }
+
+    /// Expect a call of the RPC method `cmd`.
+    pub fn expect(&self, cmd: &str) -> ExpectedCall<'_> {
+        let mut expectations = self.expectations.lock().unwrap();
+        expectations.push(Expectation {
+            cmd: cmd.to_owned(),
+            args: None,
+            response: Response::Value(Value::Null),
+            times: Times::Exactly(1),
+            calls: 0,
+        });
+        ExpectedCall {
+            mock: self,
+            index: expectations.len() - 1,
+        }
+    }
+
+    /// The calls made so far, as method and arguments.
+    pub fn calls(&self) -> Vec<(String, Vec<Value>)> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Panic if an expected call was not made as often as expected.
+    pub fn verify(&self) {
+        let expectations = self.expectations.lock().unwrap();
+        let unsatisfied: Vec<_> = expectations.iter().filter(|e| !e.is_satisfied()).collect();
+        if !unsatisfied.is_empty() {
+            let mut msg = String::from("MockRpc: expected calls were not made:\n");
+            for e in unsatisfied {
+                writeln!(msg, "  {}", e.describe()).unwrap();
+            }
+            drop(expectations);
+            panic!("{}", msg);
+        }
+    }
+
+    /// Find the expectation for a call and count the call.
+    fn find(&self, cmd: &str, args: &[Value]) -> Result<Value> {
+        let mut expectations = self.expectations.lock().unwrap();
+        let index = if self.ordered {
+            // Only the expectations from the last matched one up to the first
+            // unmet one may match, skipping those that are already met.
+            let cursor = self.cursor.load(Ordering::Relaxed);
+            let index = expectations
+                .iter()
+                .enumerate()
+                .skip(cursor)
+                .filter(|(_, e)| !e.is_exhausted())
+                .find(|(_, e)| e.matches(cmd, args) || !e.is_satisfied())
+                .map(|(i, _)| i)
+                .filter(|&i| expectations[i].matches(cmd, args));
+            if let Some(index) = index {
+                self.cursor.store(index, Ordering::Relaxed);
+            }
+            index
+        } else {
+            expectations.iter().position(|e| !e.is_exhausted() && e.matches(cmd, args))
+        };
+        let expectation = match index {
+            Some(index) => &mut expectations[index],
+            None => {
+                let msg = unexpected_call(&expectations, cmd, args);
+                // Don't poison the lock, so the mock can still be inspected.
+                drop(expectations);
+                panic!("{}", msg);
+            }
+        };
+        expectation.calls += 1;
+        match expectation.response {
+            Response::Value(ref value) => Ok(value.clone()),
+            Response::RpcError(ref err) => Err(Error::JsonRpc(jsonrpc::Error::Rpc(err.clone()))),
+            Response::Error(ref error) => Err(error()),
+        }
+    }
+}
+
+/// Describe an unexpected call and the remaining expectations, showing the
+/// arguments of expected calls of the same method next to the actual ones.
+fn unexpected_call(expectations: &[Expectation], cmd: &str, args: &[Value]) -> String {
+    let actual = Value::from(args.to_vec()).to_string();
+    let mut msg = format!("MockRpc: unexpected call {} {}\n", cmd, actual);
+    let remaining: Vec<_> = expectations.iter().filter(|e| !e.is_exhausted()).collect();
+    if remaining.is_empty() {
+        msg.push_str("no more calls were expected\n");
+    } else {
+        msg.push_str("remaining expected calls:\n");
+    }
+    for e in remaining {
+        writeln!(msg, "  {}", e.describe()).unwrap();
+        if let Some(ref expected) = e.args {
+            if e.cmd == cmd {
+                writeln!(msg, "    - expected args: {}", Value::from(expected.clone())).unwrap();
+                writeln!(msg, "    + actual args:   {}", actual).unwrap();
+            }
+        }
+    }
+    msg
+}
+
+#[async_trait]
+impl RpcApi for MockRpc {
+    async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        self.calls.lock().unwrap().push((cmd.to_owned(), args.to_vec()));
+        Ok(serde_json::from_value(self.find(cmd, args)?)?)
+    }
+
+    async fn call_named<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        self.call(cmd, &[Value::Object(args.clone())]).await
+    }
+
+    fn named_params(&self) -> bool {
+        self.named_params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::panic::AssertUnwindSafe;
+
+    #[tokio::test]
+    async fn test_mock_rpc() {
+        let mock = MockRpc::in_order();
+        mock.expect("ping").repeatedly();
+        mock.expect("getblockcount").times(2).returns(json!(7));
+        mock.expect("getblockhash").with_args(&[json!(8)]).returns_rpc_error(-8, "out of range");
+        mock.expect("getbestblockhash").returns_error(|| Error::Timeout);
+
+        mock.ping().await.unwrap();
+        mock.ping().await.unwrap();
+        assert_eq!(mock.get_block_count().await.unwrap(), 7);
+        assert_eq!(mock.get_block_count().await.unwrap(), 7);
+        let err = mock.get_block_hash(8).await.unwrap_err();
+        assert_eq!(err.rpc_error().unwrap().code, -8);
+        assert!(matches!(mock.get_best_block_hash().await, Err(Error::Timeout)));
+        mock.verify();
+        assert_eq!(mock.calls().len(), 6);
+
+        let mock = MockRpc::new();
+        mock.expect("getblockhash").with_args(&[json!(1)]);
+        let unexpected = futures::FutureExt::catch_unwind(AssertUnwindSafe(mock.get_block_hash(2)));
+        let msg = unexpected.await.unwrap_err().downcast::<String>().unwrap();
+        assert!(msg.contains("+ actual args:   [2]"), "{}", msg);
+        let unmet = std::panic::catch_unwind(AssertUnwindSafe(|| mock.verify()));
+        assert!(unmet.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mock_rpc_out_of_order() {
+        let mock = MockRpc::in_order();
+        mock.expect("ping").repeatedly();
+        mock.expect("getblockcount").returns(json!(7));
+        mock.expect("getbestblockhash").repeatedly();
+        mock.expect("getblockhash").with_args(&[json!(7)]);
+
+        // A call can't match an expectation after the next unmet one.
+        let early = futures::FutureExt::catch_unwind(AssertUnwindSafe(mock.get_block_hash(7)));
+        assert!(early.await.is_err());
+        // Skipping the pings is fine, going back to them is not.
+        assert_eq!(mock.get_block_count().await.unwrap(), 7);
+        let late = futures::FutureExt::catch_unwind(AssertUnwindSafe(mock.ping()));
+        let msg = late.await.unwrap_err().downcast::<String>().unwrap();
+        assert!(msg.contains("unexpected call ping []"), "{}", msg);
+        mock.call::<Value>("getblockhash", &[json!(7)]).await.unwrap();
+        mock.verify();
+    }
+}
@@ -0,0 +1,569 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! An in-process mock of the bitcoind JSON-RPC server for tests.
+
+use std::collections::HashSet;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::bitcoin::blockdata::constants::genesis_block;
+use crate::bitcoin::blockdata::script::Builder;
+use crate::bitcoin::consensus::encode::{deserialize, serialize_hex};
+use crate::bitcoin::hashes::hex::FromHex;
+use crate::bitcoin::hashes::Hash;
+use crate::bitcoin::util::uint::Uint256;
+use crate::bitcoin::{
+    Address, Amount, Block, BlockHash, BlockHeader, Network, OutPoint, PackedLockTime, Script,
+    Sequence, Transaction, TxIn, TxMerkleNode, TxOut, Txid, WPubkeyHash, Witness,
+};
+use crate::client::{Auth, Client, Result};
+use crate::json::{GetBlockHeaderResult, GetBlockResult};
+
+/// The number of confirmations before a coinbase output can be spent.
+const COINBASE_MATURITY: usize = 100;
+
+/// The number of blocks between two halvings of the subsidy on regtest.
+const HALVING_INTERVAL: usize = 150;
+
+/// A JSON-RPC error code and message.
+type RpcError = (i32, String);
+
+/// A mock bitcoind JSON-RPC server listening on localhost.
+///
+/// The server keeps a regtest chain, a mempool and a single wallet in
+/// memory, and implements these methods:
+///
+/// - `getblockcount`, `getbestblockhash`, `getblockhash`, `getblock`,
+///   `getblockheader`
+/// - `getrawtransaction` (not verbose), `sendrawtransaction`, `getrawmempool`
+/// - `getnewaddress`, `getbalance`, `generatetoaddress`
+/// - `getnetworkinfo` (only the version), `ping`
+///
+/// Transactions are checked for missing, spent and immature inputs and
+/// insufficient input value, but not for valid scripts or signatures. The
+/// blocks are mined with valid regtest proof of work. All methods ignore
+/// authentication and the wallet path.
+///
+/// The server stops when it is dropped.
+///
+/// ```no_run
+/// # async fn example() -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::{MockServer, RpcApi};
+///
+/// let server = MockServer::start().await?;
+/// let client = server.client().await?;
+/// let address = client.get_new_address(None, None).await?;
+/// client.generate_to_address(101, &address).await?;
+/// assert_eq!(client.get_balance(None, None).await?.to_btc(), 50.0);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MockServer {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Start a server on a random local port.
+    pub async fn start() -> io::Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let node = Arc::new(Mutex::new(Node::new()));
+        let task = tokio::spawn(async move {
+            while let Ok((sock, _)) = listener.accept().await {
+                let node = node.clone();
+                tokio::spawn(async move {
+                    let _ = serve(&node, sock).await;
+                });
+            }
+        });
+        Ok(MockServer {
+            addr,
+            task,
+        })
+    }
+
+    /// The URL of the server.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Create a client for the server.
+    pub async fn client(&self) -> Result<Client> {
+        Client::new(self.url(), Auth::None).await
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answer the HTTP requests on `sock` until the client closes it.
+async fn serve(node: &Mutex<Node>, sock: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(sock);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let mut content_length = 0;
+        let mut close = false;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(());
+            }
+            if line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                let (name, value) = (name.trim(), value.trim());
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.parse().unwrap_or(0);
+                } else if name.eq_ignore_ascii_case("connection") {
+                    close = value.eq_ignore_ascii_case("close");
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).await?;
+
+        let (status, reply) = handle_request(node, &body);
+        let reply = reply.to_string();
+        let resp = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            reply.len(),
+            reply
+        );
+        reader.get_mut().write_all(resp.as_bytes()).await?;
+        if close {
+            return Ok(());
+        }
+    }
+}
+
+/// Handle a single or batch JSON-RPC request, returning the HTTP status
+/// line and the JSON reply.
+fn handle_request(node: &Mutex<Node>, body: &[u8]) -> (&'static str, Value) {
+    match serde_json::from_slice(body) {
+        Ok(Value::Array(reqs)) => {
+            ("200 OK", reqs.iter().map(|req| handle_call(node, req).1).collect())
+        }
+        Ok(req) => handle_call(node, &req),
+        Err(_) => ("500 Internal Server Error", error_reply(Value::Null, -32700, "Parse error")),
+    }
+}
+
+fn handle_call(node: &Mutex<Node>, req: &Value) -> (&'static str, Value) {
+    let id = req["id"].clone();
+    let method = req["method"].as_str().unwrap_or("");
+    let params = match req["params"] {
+        Value::Array(ref params) => &params[..],
+        _ => &[],
+    };
+    match node.lock().unwrap().handle(method, params) {
+        Ok(result) => (
+            "200 OK",
+            json!({
+                "result": result,
+                "error": null,
+                "id": id,
+            }),
+        ),
+        Err((-32601, message)) => ("404 Not Found", error_reply(id, -32601, &message)),
+        Err((code, message)) => ("500 Internal Server Error", error_reply(id, code, &message)),
+    }
+}
+
+fn error_reply(id: Value, code: i32, message: &str) -> Value {
+    json!({
+        "result": null,
+        "error": {"code": code, "message": message},
+        "id": id,
+    })
+}
+
+/// Parse the optional parameter at `index`.
+fn opt_param<T: DeserializeOwned>(
+    params: &[Value],
+    index: usize,
+) -> std::result::Result<Option<T>, RpcError> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(param) => serde_json::from_value(param.clone())
+            .map(Some)
+            .map_err(|e| (-3, format!("Invalid type for parameter {}: {}", index + 1, e))),
+    }
+}
+
+/// Parse the required parameter at `index`.
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> std::result::Result<T, RpcError> {
+    opt_param(params, index)?.ok_or_else(|| (-1, format!("Missing parameter {}", index + 1)))
+}
+
+/// The state of the mock node.
+struct Node {
+    blocks: Vec<Block>,
+    mempool: Vec<Transaction>,
+    /// The scripts of the addresses handed out by the wallet.
+    wallet: HashSet<Script>,
+}
+
+impl Node {
+    fn new() -> Node {
+        Node {
+            blocks: vec![genesis_block(Network::Regtest)],
+            mempool: Vec::new(),
+            wallet: HashSet::new(),
+        }
+    }
+
+    fn handle(&mut self, method: &str, params: &[Value]) -> std::result::Result<Value, RpcError> {
+        let tip = self.blocks.len() - 1;
+        Ok(match method {
+            "getblockcount" => json!(tip),
+            "getbestblockhash" => json!(self.blocks[tip].block_hash()),
+            "getblockhash" => {
+                let height: usize = param(params, 0)?;
+                let block =
+                    self.blocks.get(height).ok_or((-8, "Block height out of range".into()))?;
+                json!(block.block_hash())
+            }
+            "getblock" => {
+                let height = self.find_block(&param(params, 0)?)?;
+                match opt_param::<u8>(params, 1)?.unwrap_or(1) {
+                    0 => json!(serialize_hex(&self.blocks[height])),
+                    _ => json!(self.block_info(height)),
+                }
+            }
+            "getblockheader" => {
+                let height = self.find_block(&param(params, 0)?)?;
+                match opt_param(params, 1)?.unwrap_or(true) {
+                    false => json!(serialize_hex(&self.blocks[height].header)),
+                    true => json!(self.header_info(height)),
+                }
+            }
+            "getrawtransaction" => {
+                if opt_param(params, 1)?.unwrap_or(false) {
+                    return Err((-8, "Verbose transactions are not supported".into()));
+                }
+                let txid: Txid = param(params, 0)?;
+                let (tx, _) = self.find_tx(&txid).ok_or((-5, "No such transaction".into()))?;
+                json!(serialize_hex(tx))
+            }
+            "sendrawtransaction" => {
+                let hex: String = param(params, 0)?;
+                let bytes =
+                    Vec::<u8>::from_hex(&hex).map_err(|_| (-22, "TX decode failed".into()))?;
+                let tx: Transaction =
+                    deserialize(&bytes).map_err(|_| (-22, "TX decode failed".into()))?;
+                json!(self.accept(tx)?)
+            }
+            "getrawmempool" => json!(self.mempool.iter().map(|tx| tx.txid()).collect::<Vec<_>>()),
+            "getnewaddress" => {
+                let hash = WPubkeyHash::hash(&self.wallet.len().to_le_bytes());
+                let script = Script::new_v0_p2wpkh(&hash);
+                let address = Address::from_script(&script, Network::Regtest).unwrap();
+                self.wallet.insert(script);
+                json!(address)
+            }
+            "getbalance" => {
+                let minconf = opt_param(params, 1)?.unwrap_or(0);
+                json!(self.balance(minconf).to_btc())
+            }
+            "generatetoaddress" => {
+                let count: usize = param(params, 0)?;
+                let address: Address = param::<String>(params, 1)?
+                    .parse()
+                    .map_err(|_| (-5, "Error: Invalid address".to_owned()))?;
+                let hashes: Vec<_> =
+                    (0..count).map(|_| self.mine(address.script_pubkey())).collect();
+                json!(hashes)
+            }
+            "getnetworkinfo" => json!({"version": 240000, "subversion": "/MockServer/"}),
+            "ping" => Value::Null,
+            _ => return Err((-32601, "Method not found".into())),
+        })
+    }
+
+    fn find_block(&self, hash: &BlockHash) -> std::result::Result<usize, RpcError> {
+        self.blocks
+            .iter()
+            .position(|b| b.block_hash() == *hash)
+            .ok_or((-5, "Block not found".into()))
+    }
+
+    /// Find a transaction in the chain or the mempool, with the height of its
+    /// block if it is confirmed.
+    fn find_tx(&self, txid: &Txid) -> Option<(&Transaction, Option<usize>)> {
+        let confirmed = self
+            .blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(height, block)| block.txdata.iter().map(move |tx| (tx, Some(height))));
+        let unconfirmed = self.mempool.iter().map(|tx| (tx, None));
+        confirmed.chain(unconfirmed).find(|(tx, _)| tx.txid() == *txid)
+    }
+
+    /// Whether a transaction in the chain or the mempool spends `outpoint`.
+    fn is_spent(&self, outpoint: &OutPoint) -> bool {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.txdata.iter())
+            .chain(self.mempool.iter())
+            .any(|tx| tx.input.iter().any(|input| input.previous_output == *outpoint))
+    }
+
+    /// The number of confirmations needed before `tx` can be spent.
+    fn maturity(tx: &Transaction) -> usize {
+        match tx.is_coin_base() {
+            true => COINBASE_MATURITY,
+            false => 0,
+        }
+    }
+
+    /// Check `tx` and add it to the mempool.
+    fn accept(&mut self, tx: Transaction) -> std::result::Result<Txid, RpcError> {
+        let txid = tx.txid();
+        match self.find_tx(&txid) {
+            Some((_, Some(_))) => return Err((-27, "Transaction already in block chain".into())),
+            Some((_, None)) => return Err((-26, "txn-already-in-mempool".into())),
+            None => {}
+        }
+        if tx.is_coin_base() {
+            return Err((-26, "coinbase".into()));
+        }
+        let tip = self.blocks.len() - 1;
+        let mut input_value = 0;
+        for input in &tx.input {
+            let prev = input.previous_output;
+            let (prev_tx, height) = match self.find_tx(&prev.txid) {
+                Some(found) if !self.is_spent(&prev) => found,
+                _ => return Err((-25, "bad-txns-inputs-missingorspent".into())),
+            };
+            let output = prev_tx
+                .output
+                .get(prev.vout as usize)
+                .ok_or((-25, "bad-txns-inputs-missingorspent".into()))?;
+            let confirmations = height.map_or(0, |h| tip + 1 - h);
+            if confirmations < Node::maturity(prev_tx) {
+                return Err((-26, "bad-txns-premature-spend-of-coinbase".into()));
+            }
+            input_value += output.value;
+        }
+        if tx.output.iter().map(|o| o.value).sum::<u64>() > input_value {
+            return Err((-26, "bad-txns-in-belowout".into()));
+        }
+        self.mempool.push(tx);
+        Ok(txid)
+    }
+
+    /// The value of the unspent wallet outputs with at least `minconf`
+    /// confirmations that can be spent.
+    fn balance(&self, minconf: usize) -> Amount {
+        let tip = self.blocks.len() - 1;
+        let mut balance = 0;
+        for (height, block) in self.blocks.iter().enumerate() {
+            let confirmations = tip + 1 - height;
+            for tx in &block.txdata {
+                // The wallet waits one block longer than consensus to spend
+                // coinbase outputs.
+                let maturity = Node::maturity(tx) + tx.is_coin_base() as usize;
+                if confirmations < minconf.max(maturity) {
+                    continue;
+                }
+                for (vout, output) in tx.output.iter().enumerate() {
+                    let outpoint = OutPoint::new(tx.txid(), vout as u32);
+                    if self.wallet.contains(&output.script_pubkey) && !self.is_spent(&outpoint) {
+                        balance += output.value;
+                    }
+                }
+            }
+        }
+        Amount::from_sat(balance)
+    }
+
+    /// Mine a block with the transactions in the mempool, paying the
+    /// subsidy and fees to `script_pubkey`.
+    fn mine(&mut self, script_pubkey: Script) -> BlockHash {
+        let height = self.blocks.len();
+        let fees: u64 = self
+            .mempool
+            .iter()
+            .map(|tx| {
+                let inputs: u64 = tx
+                    .input
+                    .iter()
+                    .map(|i| {
+                        self.find_tx(&i.previous_output.txid).unwrap().0.output
+                            [i.previous_output.vout as usize]
+                            .value
+                    })
+                    .sum();
+                inputs - tx.output.iter().map(|o| o.value).sum::<u64>()
+            })
+            .sum();
+        let subsidy = (50 * Amount::ONE_BTC.to_sat())
+            .checked_shr((height / HALVING_INTERVAL) as u32)
+            .unwrap_or(0);
+
+        let coinbase = Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().push_int(height as i64).push_int(0).into_script(),
+                sequence: Sequence::MAX,
+                witness: Witness::default(),
+            }],
+            output: vec![TxOut {
+                value: subsidy + fees,
+                script_pubkey,
+            }],
+        };
+        let prev = &self.blocks[height - 1].header;
+        let mut block = Block {
+            header: BlockHeader {
+                version: 0x20000000,
+                prev_blockhash: prev.block_hash(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: prev.time + 1,
+                bits: prev.bits,
+                nonce: 0,
+            },
+            txdata: Some(coinbase).into_iter().chain(self.mempool.drain(..)).collect(),
+        };
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        while block.header.validate_pow(&block.header.target()).is_err() {
+            block.header.nonce += 1;
+        }
+        let hash = block.block_hash();
+        self.blocks.push(block);
+        hash
+    }
+
+    fn header_info(&self, height: usize) -> GetBlockHeaderResult {
+        let header = &self.blocks[height].header;
+        let mut times: Vec<_> =
+            self.blocks[height.saturating_sub(10)..=height].iter().map(|b| b.header.time).collect();
+        times.sort_unstable();
+        let chainwork = self.blocks[..=height]
+            .iter()
+            .fold(Uint256::from_u64(0).unwrap(), |work, b| work + b.header.work());
+        GetBlockHeaderResult {
+            hash: header.block_hash(),
+            confirmations: (self.blocks.len() - height) as i32,
+            height,
+            version: header.version,
+            version_hex: Some(header.version.to_be_bytes().to_vec()),
+            merkle_root: header.merkle_root,
+            time: header.time as usize,
+            median_time: Some(times[times.len() / 2] as usize),
+            nonce: header.nonce,
+            bits: format!("{:08x}", header.bits),
+            difficulty: header.difficulty(Network::Regtest) as f64,
+            chainwork: chainwork.to_be_bytes().to_vec(),
+            n_tx: self.blocks[height].txdata.len(),
+            previous_block_hash: height.checked_sub(1).map(|h| self.blocks[h].block_hash()),
+            next_block_hash: self.blocks.get(height + 1).map(|b| b.block_hash()),
+        }
+    }
+
+    fn block_info(&self, height: usize) -> GetBlockResult {
+        let block = &self.blocks[height];
+        let header = self.header_info(height);
+        GetBlockResult {
+            hash: header.hash,
+            confirmations: header.confirmations,
+            size: block.size(),
+            strippedsize: Some(block.strippedsize()),
+            weight: block.weight(),
+            height,
+            version: header.version,
+            version_hex: header.version_hex,
+            merkleroot: header.merkle_root,
+            tx: block.txdata.iter().map(|tx| tx.txid()).collect(),
+            time: header.time,
+            mediantime: header.median_time,
+            nonce: header.nonce,
+            bits: header.bits,
+            difficulty: header.difficulty,
+            chainwork: header.chainwork,
+            n_tx: header.n_tx,
+            previousblockhash: header.previous_block_hash,
+            nextblockhash: header.next_block_hash,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::RpcApi;
+
+    #[tokio::test]
+    async fn test_mock_server() {
+        let server = MockServer::start().await.unwrap();
+        let client = server.client().await.unwrap();
+
+        let address = client.get_new_address(None, None).await.unwrap();
+        let hashes = client.generate_to_address(101, &address).await.unwrap();
+        assert_eq!(client.get_block_count().await.unwrap(), 101);
+        assert_eq!(client.get_best_block_hash().await.unwrap(), hashes[100]);
+        assert_eq!(client.get_balance(None, None).await.unwrap(), Amount::from_btc(50.0).unwrap());
+
+        let block = client.get_block(&hashes[0]).await.unwrap();
+        assert_eq!(client.get_block_header(&hashes[0]).await.unwrap(), block.header);
+        let info = client.get_block_header_info(&hashes[0]).await.unwrap();
+        assert_eq!((info.height, info.confirmations), (1, 101));
+        assert_eq!(
+            client.get_block_info(&hashes[0]).await.unwrap().tx,
+            vec![block.txdata[0].txid()]
+        );
+
+        // Spend the first coinbase, paying 1 BTC in fees.
+        let tx = Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(block.txdata[0].txid(), 0),
+                script_sig: Script::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::default(),
+            }],
+            output: vec![TxOut {
+                value: 49 * Amount::ONE_BTC.to_sat(),
+                script_pubkey: Script::new(),
+            }],
+        };
+        let txid = client.send_raw_transaction(&tx).await.unwrap();
+        assert_eq!(client.get_raw_mempool().await.unwrap(), vec![txid]);
+        assert!(client.send_raw_transaction(&tx).await.is_err());
+        client.generate_to_address(1, &address).await.unwrap();
+        assert_eq!(client.get_raw_transaction(&txid, None).await.unwrap(), tx);
+        assert!(client.get_raw_mempool().await.unwrap().is_empty());
+        assert_eq!(client.get_balance(None, None).await.unwrap(), Amount::from_btc(50.0).unwrap());
+
+        assert!(client.get_block_hash(1000).await.unwrap_err().rpc_error().is_some());
+        assert!(client.call::<()>("stop", &[]).await.unwrap_err().is_method_not_found());
+    }
+}
@@ -0,0 +1,134 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Redaction of passphrases, private keys and seeds in the parameters of
+//! RPC calls, so they don't end up in logs or spans.
+
+use serde_json::{Map, Value};
+
+/// What sensitive parameters are replaced with.
+const REDACTED: &str = "[redacted]";
+
+/// The position and name of the parameters of methods that must not be
+/// recorded, like passphrases and private keys.
+///
+/// Sorted, so it can be searched with [slice::binary_search_by_key].
+const SENSITIVE_PARAMS: &[(&str, &[(usize, &str)])] = &[
+    ("createwallet", &[(3, "passphrase")]),
+    ("encryptwallet", &[(0, "passphrase")]),
+    ("importdescriptors", &[(0, "requests")]),
+    ("importmulti", &[(0, "requests")]),
+    ("importprivkey", &[(0, "privkey")]),
+    ("migratewallet", &[(1, "passphrase")]),
+    ("sethdseed", &[(1, "seed")]),
+    ("signmessagewithprivkey", &[(0, "privkey")]),
+    ("signrawtransaction", &[(2, "privkeys")]),
+    ("signrawtransactionwithkey", &[(1, "privkeys")]),
+    ("walletpassphrase", &[(0, "passphrase")]),
+    ("walletpassphrasechange", &[(0, "oldpassphrase"), (1, "newpassphrase")]),
+];
+
+/// The sensitive parameters of `cmd`.
+fn sensitive_params(cmd: &str) -> &'static [(usize, &'static str)] {
+    match SENSITIVE_PARAMS.binary_search_by_key(&cmd, |&(method, _)| method) {
+        Ok(i) => SENSITIVE_PARAMS[i].1,
+        Err(_) => &[],
+    }
+}
+
+/// `args` of a call of `cmd` with the sensitive parameters redacted.
+pub(crate) fn redact_positional(cmd: &str, args: &[Value]) -> Value {
+    let mut args = args.to_vec();
+    for &(position, _) in sensitive_params(cmd) {
+        match args.get_mut(position) {
+            Some(arg) if !arg.is_null() => *arg = REDACTED.into(),
+            _ => {}
+        }
+    }
+    Value::Array(args)
+}
+
+/// Named `args` of a call of `cmd` with the sensitive parameters redacted.
+pub(crate) fn redact_named(cmd: &str, args: &Map<String, Value>) -> Value {
+    let mut args = args.clone();
+    for &(_, name) in sensitive_params(cmd) {
+        if let Some(arg) = args.get_mut(name) {
+            *arg = REDACTED.into();
+        }
+    }
+    Value::Object(args)
+}
+
+/// The positional or named `params` of a call of `cmd` with the sensitive
+/// parameters redacted.
+pub(crate) fn redact(cmd: &str, params: &Value) -> Value {
+    match *params {
+        Value::Array(ref args) => redact_positional(cmd, args),
+        Value::Object(ref args) => redact_named(cmd, args),
+        ref params => params.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensitive_params_sorted() {
+        for pair in SENSITIVE_PARAMS.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{} before {}", pair[0].0, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_redact() {
+        let args = [Value::from("secret"), Value::from(60)];
+        assert_eq!(redact_positional("walletpassphrase", &args), serde_json::json!([REDACTED, 60]));
+        assert_eq!(redact_positional("getblock", &args), serde_json::json!(["secret", 60]));
+        assert_eq!(
+            redact_positional("signmessagewithprivkey", &args),
+            serde_json::json!([REDACTED, 60])
+        );
+        assert_eq!(
+            redact_positional("migratewallet", &args),
+            serde_json::json!(["secret", REDACTED])
+        );
+        let args = [Value::from("w"), Value::Null, Value::Null, Value::Null];
+        assert_eq!(
+            redact_positional("createwallet", &args),
+            serde_json::json!(["w", null, null, null])
+        );
+        let args = [Value::from("00"), serde_json::json!([]), serde_json::json!(["key"])];
+        assert_eq!(
+            redact_positional("signrawtransaction", &args),
+            serde_json::json!(["00", [], REDACTED])
+        );
+
+        let mut args = Map::new();
+        args.insert("hexstring".into(), "00".into());
+        args.insert("privkeys".into(), serde_json::json!(["key"]));
+        assert_eq!(
+            redact_named("signrawtransactionwithkey", &args),
+            serde_json::json!({"hexstring": "00", "privkeys": REDACTED})
+        );
+        assert_eq!(
+            redact_named("signrawtransaction", &args),
+            serde_json::json!({"hexstring": "00", "privkeys": REDACTED})
+        );
+        assert_eq!(
+            redact("walletpassphrase", &serde_json::json!(["secret", 60])),
+            serde_json::json!([REDACTED, 60])
+        );
+        assert_eq!(
+            redact("signrawtransactionwithkey", &Value::Object(args)),
+            serde_json::json!({"hexstring": "00", "privkeys": REDACTED})
+        );
+    }
+}
@@ -0,0 +1,341 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! A client for the REST interface of bitcoind, enabled with `-rest`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bitcoin::consensus::encode::{self, Decodable, VarInt};
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::{Block, BlockHash, BlockHeader, OutPoint, Transaction, TxOut, Txid};
+use log::debug;
+
+use crate::bitcoin;
+use crate::builder::DEFAULT_TIMEOUT;
+use crate::client::Result;
+use crate::error::Error;
+use crate::json::GetMempoolEntryResult;
+use crate::rt;
+use crate::transport::{Endpoint, HttpOptions, HttpTransport};
+
+/// The size of a serialized block header.
+const HEADER_SIZE: usize = 80;
+
+/// An unspent output returned by [RestClient::get_utxos].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Utxo {
+    /// The height of the block that created the output, or `0x7FFFFFFF` if
+    /// the output was created by a transaction in the mempool.
+    pub height: u32,
+    pub txout: TxOut,
+}
+
+/// The result of [RestClient::get_utxos].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GetUtxosResult {
+    pub chain_height: u32,
+    pub chain_tip_hash: BlockHash,
+    /// The unspent output for every requested outpoint, [None] if it is
+    /// spent or doesn't exist.
+    pub utxos: Vec<Option<Utxo>>,
+}
+
+/// A client for the REST interface of bitcoind.
+///
+/// The REST interface serves blocks, headers and transactions in their
+/// binary serialization, which is much faster for bulk data than hex over
+/// JSON-RPC. It doesn't use authentication and has to be enabled with the
+/// `-rest` option of bitcoind.
+///
+/// ```no_run
+/// # async fn example(client: &bitcoincore_rpc_async::Client) -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::RpcApi;
+///
+/// let rest = client.rest().expect("client created from a URL");
+/// let hash = client.get_best_block_hash().await?;
+/// let block = rest.get_block(&hash).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RestClient {
+    http: Arc<HttpTransport>,
+    timeout: Option<Duration>,
+}
+
+impl RestClient {
+    /// Create a client for the REST interface of the server at `url`.
+    ///
+    /// Only the host and port of `url` are used.
+    pub async fn new(url: &str) -> Result<RestClient> {
+        let endpoint = Endpoint::new(url, HttpOptions::default())
+            .await
+            .map_err(|e| Error::JsonRpc(e.into()))?;
+        Ok(RestClient::from_endpoint(&endpoint, Some(DEFAULT_TIMEOUT)))
+    }
+
+    pub(crate) fn from_endpoint(endpoint: &Endpoint, timeout: Option<Duration>) -> RestClient {
+        RestClient {
+            http: endpoint.http(),
+            timeout,
+        }
+    }
+
+    /// Set the timeout for requests, [DEFAULT_TIMEOUT] or the default timeout
+    /// of the [crate::Client] it was created from if not set.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Don't time out requests.
+    pub fn no_timeout(mut self) -> Self {
+        self.timeout = None;
+        self
+    }
+
+    /// Send a GET request for `path` and return the body of the response.
+    async fn get(&self, path: &str) -> Result<Vec<u8>> {
+        debug!(target: "bitcoincore_rpc", "REST request: {}", path);
+        let resp = match self.timeout {
+            Some(timeout) => {
+                rt::timeout(timeout, self.http.get(path)).await.map_err(|_| {
+                    debug!(target: "bitcoincore_rpc", "REST request {} timed out after {:?}", path, timeout);
+                    Error::Timeout
+                })?
+            }
+            None => self.http.get(path).await,
+        };
+        match resp.map_err(|e| Error::JsonRpc(e.into()))? {
+            (200, body) => Ok(body),
+            (status, body) => {
+                let message = String::from_utf8_lossy(&body).trim().to_owned();
+                debug!(target: "bitcoincore_rpc", "REST error for {}: {} {}", path, status, message);
+                Err(Error::Rest(status, message))
+            }
+        }
+    }
+
+    /// Get the block `hash`.
+    pub async fn get_block(&self, hash: &BlockHash) -> Result<Block> {
+        let bytes = self.get(&format!("/rest/block/{}.bin", hash)).await?;
+        Ok(encode::deserialize(&bytes)?)
+    }
+
+    /// Get the transaction `txid`.
+    ///
+    /// Transactions that are not in the mempool can only be found with
+    /// `-txindex`.
+    pub async fn get_transaction(&self, txid: &Txid) -> Result<Transaction> {
+        let bytes = self.get(&format!("/rest/tx/{}.bin", txid)).await?;
+        Ok(encode::deserialize(&bytes)?)
+    }
+
+    /// Get up to `count` headers of the best chain, starting with the header
+    /// of the block `hash`.
+    ///
+    /// Returns fewer headers if the chain ends earlier and none if `hash` is
+    /// not in the best chain.
+    pub async fn get_block_headers(
+        &self,
+        hash: &BlockHash,
+        count: u32,
+    ) -> Result<Vec<BlockHeader>> {
+        let bytes = match self.get(&format!("/rest/headers/{}.bin?count={}", hash, count)).await {
+            // Bitcoin Core before 24.0 only knows the path with the count.
+            Err(Error::Rest(400, _)) => {
+                self.get(&format!("/rest/headers/{}/{}.bin", count, hash)).await?
+            }
+            bytes => bytes?,
+        };
+        if bytes.len() % HEADER_SIZE != 0 {
+            return Err(Error::UnexpectedStructure);
+        }
+        let mut headers = Vec::with_capacity(bytes.len() / HEADER_SIZE);
+        for chunk in bytes.chunks(HEADER_SIZE) {
+            headers.push(encode::deserialize(chunk)?);
+        }
+        Ok(headers)
+    }
+
+    /// Get the hash of the block at `height` in the best chain.
+    pub async fn get_block_hash(&self, height: u64) -> Result<BlockHash> {
+        let bytes = self.get(&format!("/rest/blockhashbyheight/{}.bin", height)).await?;
+        Ok(encode::deserialize(&bytes)?)
+    }
+
+    /// Look up the unspent outputs of `outpoints`, including those created
+    /// by mempool transactions if `check_mempool` is set.
+    ///
+    /// bitcoind accepts at most 15 outpoints per request.
+    pub async fn get_utxos(
+        &self,
+        outpoints: &[OutPoint],
+        check_mempool: bool,
+    ) -> Result<GetUtxosResult> {
+        let mut path = String::from("/rest/getutxos");
+        if check_mempool {
+            path.push_str("/checkmempool");
+        }
+        for outpoint in outpoints {
+            path.push_str(&format!("/{}-{}", outpoint.txid, outpoint.vout));
+        }
+        path.push_str(".bin");
+        let bytes = self.get(&path).await?;
+        decode_utxos(&bytes, outpoints.len())
+    }
+
+    /// Get the entries of all transactions in the mempool.
+    pub async fn get_mempool_contents(&self) -> Result<HashMap<Txid, GetMempoolEntryResult>> {
+        let bytes = self.get("/rest/mempool/contents.json").await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+impl fmt::Debug for RestClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bitcoincore_rpc::RestClient({}://{})", self.http.scheme(), self.http.host())
+    }
+}
+
+/// Decode the binary reply of `getutxos` for `n` outpoints.
+fn decode_utxos(bytes: &[u8], n: usize) -> Result<GetUtxosResult> {
+    let mut cursor = Cursor::new(bytes);
+    let chain_height = u32::consensus_decode(&mut cursor)?;
+    let chain_tip_hash = BlockHash::consensus_decode(&mut cursor)?;
+    let bitmap = Vec::<u8>::consensus_decode(&mut cursor)?;
+    let count = VarInt::consensus_decode(&mut cursor)?.0;
+    let mut coins = Vec::with_capacity(count.min(n as u64) as usize);
+    for _ in 0..count {
+        // The coins are serialized with a transaction version that is unused.
+        let _version = u32::consensus_decode(&mut cursor)?;
+        coins.push(Utxo {
+            height: u32::consensus_decode(&mut cursor)?,
+            txout: TxOut::consensus_decode(&mut cursor)?,
+        });
+    }
+    if cursor.position() as usize != bytes.len() {
+        debug!(target: "bitcoincore_rpc", "getutxos reply has trailing data: {}", bytes.to_hex());
+        return Err(Error::UnexpectedStructure);
+    }
+
+    // The bitmap tells which of the outpoints are unspent, in order.
+    let mut coins = coins.into_iter();
+    let mut utxos = Vec::with_capacity(n);
+    for i in 0..n {
+        let unspent = bitmap.get(i / 8).map(|byte| byte & (1 << (i % 8)) != 0);
+        match unspent {
+            Some(true) => utxos.push(Some(coins.next().ok_or(Error::UnexpectedStructure)?)),
+            Some(false) => utxos.push(None),
+            None => return Err(Error::UnexpectedStructure),
+        }
+    }
+    if coins.next().is_some() {
+        return Err(Error::UnexpectedStructure);
+    }
+    Ok(GetUtxosResult {
+        chain_height,
+        chain_tip_hash,
+        utxos,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::blockdata::constants::genesis_block;
+    use crate::bitcoin::consensus::encode::serialize;
+    use crate::bitcoin::Network;
+    use crate::test_server::serve;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_rest_client() {
+        let genesis = genesis_block(Network::Regtest);
+        let hash = genesis.block_hash();
+        let coinbase = genesis.txdata[0].clone();
+        let outpoints = [OutPoint::new(coinbase.txid(), 0), OutPoint::new(coinbase.txid(), 1)];
+
+        let mut utxos = serialize(&0u32);
+        utxos.extend(serialize(&hash));
+        utxos.extend(serialize(&vec![0b01u8]));
+        utxos.extend(serialize(&VarInt(1)));
+        utxos.extend(serialize(&0u32));
+        utxos.extend(serialize(&0u32));
+        utxos.extend(serialize(&coinbase.output[0]));
+
+        let utxos_path =
+            format!("/rest/getutxos/checkmempool/{}-0/{}-1.bin", coinbase.txid(), coinbase.txid());
+        let replies = [
+            (format!("/rest/block/{}.bin", hash), 200, serialize(&genesis)),
+            (format!("/rest/headers/{}.bin?count=2", hash), 200, serialize(&genesis.header)),
+            // The reply of Bitcoin Core before 24.0 to the path without the count.
+            (
+                format!("/rest/headers/{}.bin?count=3", hash),
+                400,
+                b"No header count specified. Use /rest/headers/<count>/<hash>.<ext>.".to_vec(),
+            ),
+            (format!("/rest/headers/3/{}.bin", hash), 200, serialize(&genesis.header)),
+            (utxos_path, 200, utxos),
+            (
+                "/rest/blockhashbyheight/1.bin".to_owned(),
+                404,
+                b"Block height out of range".to_vec(),
+            ),
+        ];
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        // Reply to each path with its status and body from `replies`.
+        let server = tokio::spawn(serve(listener, replies.len(), move |req| {
+            let (_, status, body) = replies.iter().find(|r| r.0 == req.path).unwrap();
+            (*status, body.clone())
+        }));
+
+        let rest = RestClient::new(&url).await.unwrap();
+        assert_eq!(rest.get_block(&hash).await.unwrap(), genesis);
+        assert_eq!(rest.get_block_headers(&hash, 2).await.unwrap(), vec![genesis.header]);
+        assert_eq!(rest.get_block_headers(&hash, 3).await.unwrap(), vec![genesis.header]);
+        let result = rest.get_utxos(&outpoints, true).await.unwrap();
+        assert_eq!(result.chain_tip_hash, hash);
+        assert_eq!(
+            result.utxos,
+            vec![
+                Some(Utxo {
+                    height: 0,
+                    txout: coinbase.output[0].clone(),
+                }),
+                None,
+            ]
+        );
+        match rest.get_block_hash(1).await {
+            Err(Error::Rest(404, ref message)) if message == "Block height out of range" => {}
+            r => panic!("expected REST error, got {:?}", r),
+        }
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_debug() {
+        let rest = RestClient::new("http://127.0.0.1:8332/").await.unwrap();
+        assert_eq!(format!("{:?}", rest), "bitcoincore_rpc::RestClient(http://127.0.0.1:8332)");
+        #[cfg(feature = "tls")]
+        {
+            let rest = RestClient::new("https://127.0.0.1:8332/").await.unwrap();
+            assert_eq!(
+                format!("{:?}", rest),
+                "bitcoincore_rpc::RestClient(https://127.0.0.1:8332)"
+            );
+        }
+    }
+}
@@ -0,0 +1,359 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Retrying failed calls with exponential backoff.
+
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use jsonrpc::simple_http;
+use jsonrpc_async as jsonrpc;
+use log::debug;
+use serde_json::value::RawValue;
+
+use crate::client::{Params, Result, RpcApi};
+use crate::error::Error;
+use crate::layer::{Layer, RpcRequest, RpcService};
+use crate::rt;
+
+/// Methods that must not be repeated when it is unknown whether the node
+/// already executed them, because they spend coins, mine blocks or otherwise
+/// change state in a way that can't be repeated safely.
+///
+/// Sorted, so it can be searched with [slice::binary_search].
+const NON_IDEMPOTENT_METHODS: &[&str] = &[
+    "bumpfee",
+    "createwallet",
+    "encryptwallet",
+    "fundrawtransaction",
+    "generate",
+    "generateblock",
+    "generatetoaddress",
+    "generatetodescriptor",
+    "getnewaddress",
+    "getrawchangeaddress",
+    "psbtbumpfee",
+    "send",
+    "sendmany",
+    "sendtoaddress",
+    "stop",
+    "walletcreatefundedpsbt",
+    "walletpassphrasechange",
+];
+
+/// Whether `cmd` can be sent again when it's unknown if the node executed it.
+pub(crate) fn is_idempotent(cmd: &str) -> bool {
+    NON_IDEMPOTENT_METHODS.binary_search(&cmd).is_err()
+}
+
+/// Configures when and how often [RetryClient] and [RetryLayer] retry a
+/// failed call.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// The upper bound for the delay between two attempts.
+    pub max_backoff: Duration,
+    /// Stop retrying once this much time has passed since the first attempt.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a call of `cmd` that failed with `err` may be retried.
+    ///
+    /// Errors for which the node certainly didn't execute the call (it is
+    /// still warming up, its work queue is full or the connection was
+    /// refused) can be retried for every method. I/O errors and timeouts
+    /// leave it unknown whether the call was executed, so these are only
+    /// retried for idempotent methods. Other errors, like responses that
+    /// can't be parsed, would fail the same way again and are never retried.
+    pub fn is_retryable(&self, cmd: &str, err: &Error) -> bool {
+        if err.is_warmup() {
+            return true;
+        }
+        if let Error::Timeout = *err {
+            return is_idempotent(cmd);
+        }
+        match transport_error(err) {
+            // "Work queue depth exceeded"
+            Some(simple_http::Error::HttpErrorCode(503)) => true,
+            Some(simple_http::Error::SocketError(ref e)) => match e.kind() {
+                io::ErrorKind::ConnectionRefused => true,
+                // An invalid or too large HTTP response.
+                io::ErrorKind::InvalidData => false,
+                _ => is_idempotent(cmd),
+            },
+            Some(simple_http::Error::Timeout) => is_idempotent(cmd),
+            _ => false,
+        }
+    }
+
+    /// The delay before the next attempt after `attempt` attempts have failed.
+    ///
+    /// Grows exponentially with a random jitter of up to half the delay, so
+    /// that many clients don't retry at the same time.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.initial_backoff.saturating_mul(1 << attempt.saturating_sub(1).min(16));
+        let backoff = exp.min(self.max_backoff);
+        let half = backoff / 2;
+        half + half.mul_f64(random_fraction())
+    }
+
+    /// Run `attempt` for a call of `cmd` until it succeeds or fails with an
+    /// error that may not be retried.
+    pub(crate) async fn retry<T, F, Fut>(&self, cmd: &str, mut attempt: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let mut attempts = 1;
+        loop {
+            let delay = match attempt().await {
+                Err(e) if attempts < self.max_attempts && self.is_retryable(cmd, &e) => {
+                    let delay = self.backoff(attempts);
+                    if let Some(deadline) = self.deadline {
+                        if start.elapsed() + delay > deadline {
+                            return Err(e);
+                        }
+                    }
+                    debug!(target: "bitcoincore_rpc", "retrying {} in {:?} after error: {}", cmd, delay, e);
+                    delay
+                }
+                res => return res,
+            };
+            rt::sleep(delay).await;
+            attempts += 1;
+        }
+    }
+}
+
+/// The transport error behind `err`, if there is one.
+fn transport_error(err: &Error) -> Option<&simple_http::Error> {
+    match *err {
+        Error::JsonRpc(jsonrpc::Error::Transport(ref e)) => e.downcast_ref(),
+        _ => None,
+    }
+}
+
+/// A random number in `[0, 1)`, good enough for jitter.
+fn random_fraction() -> f64 {
+    // Every `RandomState` is seeded with different random keys.
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// An [RpcApi] that retries failed calls of an inner [RpcApi] according to
+/// a [RetryPolicy].
+///
+/// Waiting between attempts doesn't block the executor.
+pub struct RetryClient<C> {
+    inner: C,
+    policy: RetryPolicy,
+}
+
+impl<C: RpcApi> RetryClient<C> {
+    /// Wrap `inner`, retrying its calls according to `policy`.
+    pub fn new(inner: C, policy: RetryPolicy) -> RetryClient<C> {
+        RetryClient {
+            inner,
+            policy,
+        }
+    }
+
+    /// Get the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Get the retry policy.
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+}
+
+impl<C: RpcApi + Sync> RetryClient<C> {
+    async fn call_params<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        params: Params<'_>,
+    ) -> Result<T> {
+        self.policy.retry(cmd, || params.call(&self.inner, cmd)).await
+    }
+}
+
+#[async_trait]
+impl<C: RpcApi + Sync> RpcApi for RetryClient<C> {
+    async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        self.call_params(cmd, Params::Positional(args)).await
+    }
+
+    async fn call_named<T: for<'a> serde::de::Deserialize<'a>>(
+        &self,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        self.call_params(cmd, Params::Named(args)).await
+    }
+
+    fn named_params(&self) -> bool {
+        self.inner.named_params()
+    }
+}
+
+/// A [Layer] that retries failed calls according to a [RetryPolicy], like
+/// [RetryClient].
+#[derive(Clone, Debug)]
+pub struct RetryLayer {
+    policy: RetryPolicy,
+}
+
+impl RetryLayer {
+    /// Retry calls according to `policy`.
+    pub fn new(policy: RetryPolicy) -> RetryLayer {
+        RetryLayer {
+            policy,
+        }
+    }
+}
+
+impl<S: RpcService> Layer<S> for RetryLayer {
+    type Service = RetryService<S>;
+
+    fn layer(&self, inner: S) -> RetryService<S> {
+        RetryService {
+            inner,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+/// The service of a [RetryLayer].
+pub struct RetryService<S> {
+    inner: S,
+    policy: RetryPolicy,
+}
+
+#[async_trait]
+impl<S: RpcService> RpcService for RetryService<S> {
+    async fn call(&self, request: &RpcRequest) -> Result<Box<RawValue>> {
+        self.policy.retry(&request.method, || self.inner.call(request)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails the first `failures` calls with the error made by `error`.
+    struct Flaky {
+        failures: u32,
+        error: fn() -> Error,
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl RpcApi for Flaky {
+        async fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            _: &str,
+            _: &[serde_json::Value],
+        ) -> Result<T> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err((self.error)())
+            } else {
+                Ok(serde_json::from_value(42.into())?)
+            }
+        }
+    }
+
+    fn warmup() -> Error {
+        Error::JsonRpc(jsonrpc::Error::Rpc(jsonrpc::error::RpcError {
+            code: -28,
+            message: "Loading wallet...".into(),
+            data: None,
+        }))
+    }
+
+    fn timeout() -> Error {
+        Error::Timeout
+    }
+
+    fn parse_error() -> Error {
+        let e = serde_json::from_str::<u64>("{").unwrap_err();
+        Error::JsonRpc(jsonrpc::Error::Transport(Box::new(simple_http::Error::Json(e))))
+    }
+
+    fn reset() -> Error {
+        let e = io::Error::from(io::ErrorKind::ConnectionReset);
+        Error::JsonRpc(jsonrpc::Error::Transport(Box::new(simple_http::Error::SocketError(e))))
+    }
+
+    fn flaky(failures: u32, error: fn() -> Error) -> RetryClient<Flaky> {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let inner = Flaky {
+            failures,
+            error,
+            calls: AtomicU32::new(0),
+        };
+        RetryClient::new(inner, policy)
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let client = flaky(2, warmup);
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+        assert_eq!(client.inner().calls.load(Ordering::SeqCst), 3);
+
+        let client = flaky(3, warmup);
+        assert!(client.get_block_count().await.unwrap_err().is_warmup());
+
+        let client = flaky(1, timeout);
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+
+        let client = flaky(1, timeout);
+        let _: Error = client.call::<u64>("sendtoaddress", &[]).await.unwrap_err();
+        assert_eq!(client.inner().calls.load(Ordering::SeqCst), 1);
+
+        let client = flaky(1, reset);
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+
+        // Replies that can't be parsed would fail the same way again.
+        let client = flaky(1, parse_error);
+        let _: Error = client.get_block_count().await.unwrap_err();
+        assert_eq!(client.inner().calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,98 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Timers and TCP streams of the async runtime the crate is built for:
+//! tokio with the `tokio` feature, otherwise async-io with the `minimal`
+//! feature.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+#[cfg(not(any(feature = "tokio", feature = "minimal")))]
+compile_error!("either the `tokio` or the `minimal` feature must be enabled");
+
+#[cfg(all(feature = "minimal", not(feature = "tokio")))]
+pub(crate) use async_net::TcpStream;
+#[cfg(all(feature = "minimal", not(feature = "tokio")))]
+pub(crate) use futures::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "tokio")]
+pub(crate) use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(feature = "tokio")]
+pub(crate) use tokio::net::TcpStream;
+
+/// The error of a [timeout] that expired.
+#[derive(Debug)]
+pub(crate) struct Elapsed;
+
+/// Wait for `duration`.
+#[cfg(feature = "tokio")]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await
+}
+
+/// Wait for `duration`.
+#[cfg(all(feature = "minimal", not(feature = "tokio")))]
+pub(crate) async fn sleep(duration: Duration) {
+    async_io::Timer::after(duration).await;
+}
+
+/// Wait until `deadline`.
+#[cfg(feature = "tokio")]
+pub(crate) async fn sleep_until(deadline: Instant) {
+    tokio::time::sleep_until(deadline.into()).await
+}
+
+/// Wait until `deadline`.
+#[cfg(all(feature = "minimal", not(feature = "tokio")))]
+pub(crate) async fn sleep_until(deadline: Instant) {
+    async_io::Timer::at(deadline).await;
+}
+
+/// Run `future`, unless it doesn't finish within `duration`.
+#[cfg(feature = "tokio")]
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    tokio::time::timeout(duration, future).await.map_err(|_| Elapsed)
+}
+
+/// Run `future`, unless it doesn't finish within `duration`.
+#[cfg(all(feature = "minimal", not(feature = "tokio")))]
+pub(crate) async fn timeout<F: Future>(
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    use futures::future::{self, Either};
+
+    futures::pin_mut!(future);
+    match future::select(future, async_io::Timer::after(duration)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(Elapsed),
+    }
+}
+
+// Without a runtime feature, these stand-ins keep the rest of the crate
+// resolving, so the `compile_error!` above is the only error reported.
+
+#[cfg(not(any(feature = "tokio", feature = "minimal")))]
+pub(crate) async fn sleep(_: Duration) {
+    unreachable!()
+}
+
+#[cfg(not(any(feature = "tokio", feature = "minimal")))]
+pub(crate) async fn sleep_until(_: Instant) {
+    unreachable!()
+}
+
+#[cfg(not(any(feature = "tokio", feature = "minimal")))]
+pub(crate) async fn timeout<F: Future>(_: Duration, _: F) -> Result<F::Output, Elapsed> {
+    unreachable!()
+}
@@ -0,0 +1,270 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Connecting to the server through a SOCKS5 proxy, like Tor.
+
+use std::{io, net};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The port of the SOCKS5 proxy of a Tor daemon on the local host, used
+/// for `.onion` hosts if no proxy is set.
+const TOR_SOCKS_PORT: u16 = 9050;
+
+/// A SOCKS5 proxy to connect to the server through, see
+/// [crate::ClientBuilder::proxy].
+///
+/// The host of the URL is resolved by the proxy, so `.onion` hosts can be
+/// reached through Tor.
+///
+/// ```no_run
+/// # async fn example() -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::{Auth, ClientBuilder, Socks5Proxy};
+///
+/// let proxy = Socks5Proxy::new("127.0.0.1:9050".parse().unwrap()).credentials("wallet", "1");
+/// let client = ClientBuilder::new("http://abcdefghijklmnop.onion:8332".into(), Auth::None)
+///     .proxy(proxy)
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Socks5Proxy {
+    addr: net::SocketAddr,
+    credentials: Option<(String, String)>,
+}
+
+impl Socks5Proxy {
+    /// Connect through the proxy at `addr`, without authentication.
+    pub fn new(addr: net::SocketAddr) -> Socks5Proxy {
+        Socks5Proxy {
+            addr,
+            credentials: None,
+        }
+    }
+
+    /// The Tor proxy on the local host, at its default port.
+    pub(crate) fn tor() -> Socks5Proxy {
+        Socks5Proxy::new((net::Ipv4Addr::LOCALHOST, TOR_SOCKS_PORT).into())
+    }
+
+    /// Authenticate to the proxy with `user` and `pass`.
+    ///
+    /// Tor accepts any credentials, but only shares circuits between
+    /// connections with the same ones, so they can be used to isolate the
+    /// connections of this client from other traffic.
+    pub fn credentials(mut self, user: &str, pass: &str) -> Socks5Proxy {
+        self.credentials = Some((user.to_owned(), pass.to_owned()));
+        self
+    }
+
+    /// The address of the proxy.
+    pub fn addr(&self) -> net::SocketAddr {
+        self.addr
+    }
+
+    /// Ask the proxy to connect `sock`, a connection to the proxy, to `host`
+    /// and `port`.
+    pub(crate) async fn handshake(
+        &self,
+        sock: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> io::Result<()> {
+        // Offer a single authentication method.
+        let method = match self.credentials {
+            Some(_) => 0x02,
+            None => 0x00,
+        };
+        sock.write_all(&[0x05, 0x01, method]).await?;
+        let mut reply = [0; 2];
+        sock.read_exact(&mut reply).await?;
+        if reply[0] != 0x05 {
+            return Err(socks_error("not a SOCKS5 proxy"));
+        }
+        if reply[1] != method {
+            return Err(socks_error("the proxy refused the authentication method"));
+        }
+
+        if let Some((ref user, ref pass)) = self.credentials {
+            let mut auth = vec![0x01];
+            push_field(&mut auth, user.as_bytes())?;
+            push_field(&mut auth, pass.as_bytes())?;
+            sock.write_all(&auth).await?;
+            sock.read_exact(&mut reply).await?;
+            if reply[1] != 0x00 {
+                return Err(socks_error("the proxy rejected the credentials"));
+            }
+        }
+
+        // Send the host as a domain name unless it's an IP address, so the
+        // proxy resolves it.
+        let mut request = vec![0x05, 0x01, 0x00];
+        match host.parse::<net::IpAddr>() {
+            Ok(net::IpAddr::V4(ip)) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(net::IpAddr::V6(ip)) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                request.push(0x03);
+                push_field(&mut request, host.as_bytes())?;
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        sock.write_all(&request).await?;
+
+        let mut reply = [0; 4];
+        sock.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            return Err(socks_error(&format!(
+                "the proxy failed to connect: {}",
+                reply_message(reply[1])
+            )));
+        }
+        // Skip the address the proxy bound to.
+        let len = match reply[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => sock.read_u8().await? as usize,
+            _ => return Err(socks_error("invalid address type in reply")),
+        };
+        let mut bound = vec![0; len + 2];
+        sock.read_exact(&mut bound).await?;
+        Ok(())
+    }
+}
+
+/// Whether `hostname` is a Tor onion service.
+pub(crate) fn is_onion(hostname: &str) -> bool {
+    hostname.to_ascii_lowercase().ends_with(".onion")
+}
+
+/// Append `field` with its length byte to `buf`.
+fn push_field(buf: &mut Vec<u8>, field: &[u8]) -> io::Result<()> {
+    if field.len() > 255 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "SOCKS5 field too long"));
+    }
+    buf.push(field.len() as u8);
+    buf.extend_from_slice(field);
+    Ok(())
+}
+
+/// The meaning of the reply code `code` of a SOCKS5 request.
+fn reply_message(code: u8) -> String {
+    match code {
+        0x01 => "general failure".into(),
+        0x02 => "not allowed by ruleset".into(),
+        0x03 => "network unreachable".into(),
+        0x04 => "host unreachable".into(),
+        0x05 => "connection refused".into(),
+        0x06 => "TTL expired".into(),
+        0x07 => "command not supported".into(),
+        0x08 => "address type not supported".into(),
+        code => format!("error {:#04x}", code),
+    }
+}
+
+fn socks_error(message: &str) -> io::Error {
+    io::Error::other(format!("SOCKS5: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Auth, RpcApi};
+    use crate::test_server::{read_request, write_response};
+    use crate::ClientBuilder;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// A SOCKS5 proxy that accepts the password `secret`, records the user
+    /// and the requested host and port, and then answers JSON-RPC calls
+    /// itself instead of connecting to the host.
+    async fn serve_socks(listener: TcpListener, seen: Arc<Mutex<Vec<(String, String, u16)>>>) {
+        loop {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut greeting = [0; 3];
+            sock.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [0x05, 0x01, 0x02]);
+            sock.write_all(&[0x05, 0x02]).await.unwrap();
+
+            let mut header = [0; 2];
+            sock.read_exact(&mut header).await.unwrap();
+            let mut user = vec![0; header[1] as usize];
+            sock.read_exact(&mut user).await.unwrap();
+            let mut pass = vec![0; sock.read_u8().await.unwrap() as usize];
+            sock.read_exact(&mut pass).await.unwrap();
+            if pass != b"secret" {
+                sock.write_all(&[0x01, 0x01]).await.unwrap();
+                continue;
+            }
+            sock.write_all(&[0x01, 0x00]).await.unwrap();
+
+            let mut request = [0; 5];
+            sock.read_exact(&mut request).await.unwrap();
+            assert_eq!(request[..4], [0x05, 0x01, 0x00, 0x03]);
+            let mut host = vec![0; request[4] as usize];
+            sock.read_exact(&mut host).await.unwrap();
+            let port = sock.read_u16().await.unwrap();
+            seen.lock().unwrap().push((
+                String::from_utf8(user).unwrap(),
+                String::from_utf8(host).unwrap(),
+                port,
+            ));
+            sock.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await.unwrap();
+
+            read_request(&mut sock).await.unwrap();
+            let body = r#"{"result":42,"error":null,"id":0}"#;
+            write_response(&mut sock, 200, body.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socks5_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy = Socks5Proxy::new(listener.local_addr().unwrap());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let server = tokio::spawn(serve_socks(listener, seen.clone()));
+
+        // The onion host is resolved by the proxy, not locally.
+        let url = "http://abcdefghijklmnop.onion:18332/".to_owned();
+        let client = ClientBuilder::new(url.clone(), Auth::None)
+            .proxy(proxy.clone().credentials("isolated", "secret"))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [("isolated".to_owned(), "abcdefghijklmnop.onion".to_owned(), 18332)]
+        );
+
+        let client = ClientBuilder::new(url, Auth::None)
+            .proxy(proxy.credentials("isolated", "wrong"))
+            .build()
+            .await
+            .unwrap();
+        assert!(client.get_block_count().await.is_err());
+        server.abort();
+    }
+
+    #[test]
+    fn test_is_onion() {
+        assert!(is_onion("abcdefghijklmnop.onion"));
+        assert!(is_onion("ABCDEFGHIJKLMNOP.ONION"));
+        assert!(!is_onion("localhost"));
+    }
+}
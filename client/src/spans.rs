@@ -0,0 +1,84 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! `tracing` spans for RPC calls, with the `tracing` feature.
+//!
+//! Every call of a [crate::Client] runs in a `rpc` span at the debug level
+//! with the target `bitcoincore_rpc` and the fields `method`, `wallet`,
+//! `id`, `params`, `latency_ms`, and `error_code` or `error` if it failed.
+//! Passphrases, private keys and seeds in the parameters are redacted.
+
+use std::time::Duration;
+
+use jsonrpc_async as jsonrpc;
+use serde_json::{Map, Value};
+use tracing::{field, Span};
+
+use crate::client::Result;
+use crate::redact::{redact_named, redact_positional};
+
+/// Create the span of a call of `cmd`, on the endpoint of `wallet` if set.
+pub(crate) fn call_span(cmd: &str, wallet: Option<&str>) -> Span {
+    tracing::debug_span!(
+        target: "bitcoincore_rpc",
+        "rpc",
+        method = cmd,
+        wallet = wallet,
+        id = field::Empty,
+        params = field::Empty,
+        latency_ms = field::Empty,
+        error_code = field::Empty,
+        error = field::Empty,
+    )
+}
+
+/// Record the id and the redacted positional `args` of a request of `cmd`
+/// in the current span.
+pub(crate) fn record_request(id: &Value, cmd: &str, args: &[Value]) {
+    let span = Span::current();
+    if !span.is_disabled() {
+        span.record("id", field::display(id));
+        span.record("params", field::display(redact_positional(cmd, args)));
+    }
+}
+
+/// Record the id and the redacted named `args` of a request of `cmd` in the
+/// current span.
+pub(crate) fn record_named_request(id: &Value, cmd: &str, args: &Map<String, Value>) {
+    let span = Span::current();
+    if !span.is_disabled() {
+        span.record("id", field::display(id));
+        span.record("params", field::display(redact_named(cmd, args)));
+    }
+}
+
+/// Record the latency and the error, if any, of a call in its `span`.
+pub(crate) fn record_response(span: &Span, latency: Duration, resp: &Result<jsonrpc::Response>) {
+    if span.is_disabled() {
+        return;
+    }
+    span.record("latency_ms", latency.as_millis() as u64);
+    match *resp {
+        Ok(jsonrpc::Response {
+            error: Some(ref e),
+            ..
+        }) => {
+            span.record("error_code", e.code);
+            span.record("error", field::display(&e.message));
+        }
+        Ok(_) => {}
+        Err(ref e) => {
+            if let Some(code) = e.rpc_code() {
+                span.record("error_code", code.code());
+            }
+            span.record("error", field::display(e));
+        }
+    }
+}
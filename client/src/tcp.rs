@@ -0,0 +1,581 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! The built-in HTTP transport on tokio, with the `tokio` feature.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::{fmt, io, net};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use log::debug;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+
+use crate::socks::Socks5Proxy;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConnect;
+use crate::transport::{
+    invalid_response, response_too_large, PoolStats, Transport, DEFAULT_MAX_CONNECTIONS,
+    DEFAULT_MAX_RESPONSE_SIZE,
+};
+
+/// A plain TCP or a TLS stream.
+trait Io: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> Io for T {}
+
+impl fmt::Debug for dyn Io {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Io")
+    }
+}
+
+/// A connection to the server.
+type Connection = BufReader<Box<dyn Io>>;
+
+/// The built-in [Transport], sending requests with HTTP/1.1 over a pool of
+/// keep-alive TCP connections.
+///
+/// At most [TcpTransport::max_connections] requests are in flight at the
+/// same time, each on its own connection. Further requests wait for a free
+/// connection in the order they were made.
+#[derive(Debug)]
+pub struct TcpTransport {
+    addr: net::SocketAddr,
+    /// The value of the `Host` HTTP header.
+    host: String,
+    /// A permit for every connection that can be used.
+    permits: Semaphore,
+    max_connections: usize,
+    max_response_size: usize,
+    /// The open connections that are not in use.
+    idle: Mutex<Vec<Connection>>,
+    queued: AtomicUsize,
+    connections_opened: AtomicU64,
+    connections_reused: AtomicU64,
+    /// The proxy `addr` belongs to, and the host and port of the server to
+    /// connect to through it.
+    proxy: Option<(Socks5Proxy, String, u16)>,
+    /// Set for `https` URLs.
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnect>,
+}
+
+impl TcpTransport {
+    /// Send requests to `addr`, with `host` as the `Host` HTTP header.
+    pub fn new(addr: net::SocketAddr, host: String) -> TcpTransport {
+        TcpTransport {
+            addr,
+            host,
+            permits: Semaphore::new(DEFAULT_MAX_CONNECTIONS),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            idle: Mutex::new(Vec::new()),
+            queued: AtomicUsize::new(0),
+            connections_opened: AtomicU64::new(0),
+            connections_reused: AtomicU64::new(0),
+            proxy: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// Keep at most `max` connections to the server, [DEFAULT_MAX_CONNECTIONS]
+    /// if not set.
+    ///
+    /// Panics if `max` is zero.
+    pub fn max_connections(mut self, max: usize) -> TcpTransport {
+        assert!(max > 0, "max_connections must be positive");
+        self.permits = Semaphore::new(max);
+        self.max_connections = max;
+        self
+    }
+
+    /// Fail requests whose response body is larger than `max` bytes, before
+    /// reading it. [DEFAULT_MAX_RESPONSE_SIZE] if not set.
+    pub fn max_response_size(mut self, max: usize) -> TcpTransport {
+        self.max_response_size = max;
+        self
+    }
+
+    /// Connect through `proxy` to `hostname` and `port`, which are resolved
+    /// by the proxy.
+    pub(crate) fn proxy(mut self, proxy: Socks5Proxy, hostname: &str, port: u16) -> TcpTransport {
+        self.addr = proxy.addr();
+        self.proxy = Some((proxy, hostname.to_owned(), port));
+        self
+    }
+
+    /// Use TLS on the connections.
+    #[cfg(feature = "tls")]
+    pub(crate) fn tls(mut self, tls: TlsConnect) -> TcpTransport {
+        self.tls = Some(tls);
+        self
+    }
+
+    async fn connect(&self) -> io::Result<Connection> {
+        let mut sock = TcpStream::connect(self.addr).await?;
+        if let Some((ref proxy, ref hostname, port)) = self.proxy {
+            proxy.handshake(&mut sock, hostname, port).await?;
+        }
+        #[cfg(feature = "tls")]
+        let stream: Box<dyn Io> = match self.tls {
+            Some(ref tls) => Box::new(tls.connect(sock).await?),
+            None => Box::new(sock),
+        };
+        #[cfg(not(feature = "tls"))]
+        let stream: Box<dyn Io> = Box::new(sock);
+        self.connections_opened.fetch_add(1, Ordering::Relaxed);
+        Ok(BufReader::new(stream))
+    }
+
+    /// Send `request` on `conn` and read the response, returning whether the
+    /// connection can be used again.
+    ///
+    /// `written` is set once the whole request was written.
+    async fn exchange(
+        &self,
+        conn: &mut Connection,
+        request: &[u8],
+        written: &mut bool,
+    ) -> io::Result<(u16, Vec<u8>, bool)> {
+        conn.get_mut().write_all(request).await?;
+        conn.get_mut().flush().await?;
+        *written = true;
+
+        let mut line = String::new();
+        if conn.read_line(&mut line).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if !line.starts_with("HTTP/1.") {
+            return Err(invalid_response());
+        }
+        let response_code: u16 = line
+            .get(9..12)
+            .ok_or_else(invalid_response)?
+            .parse()
+            .map_err(|_| invalid_response())?;
+        let mut keep_alive = line.starts_with("HTTP/1.1");
+
+        let mut content_length = None;
+        let mut chunked = false;
+        loop {
+            line.clear();
+            if conn.read_line(&mut line).await? == 0 {
+                return Err(invalid_response());
+            }
+            if line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                let (name, value) = (name.trim(), value.trim());
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = Some(value.parse().map_err(|_| invalid_response())?);
+                } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                    chunked = value.eq_ignore_ascii_case("chunked");
+                } else if name.eq_ignore_ascii_case("connection") {
+                    keep_alive &= !value.eq_ignore_ascii_case("close");
+                }
+            }
+        }
+
+        if response_code == 401 {
+            // There is no body in a 401 response, so don't try to read it
+            return Ok((response_code, Vec::new(), false));
+        }
+
+        let resp_body = if chunked {
+            read_chunked(conn, self.max_response_size).await?
+        } else if let Some(len) = content_length {
+            if len > self.max_response_size {
+                return Err(response_too_large(self.max_response_size));
+            }
+            let mut buf = vec![0; len];
+            conn.read_exact(&mut buf).await?;
+            buf
+        } else {
+            // The body ends when the connection is closed.
+            keep_alive = false;
+            let mut buf = Vec::new();
+            let limit = self.max_response_size as u64 + 1;
+            (&mut *conn).take(limit).read_to_end(&mut buf).await?;
+            if buf.len() > self.max_response_size {
+                return Err(response_too_large(self.max_response_size));
+            }
+            buf
+        };
+        Ok((response_code, resp_body, keep_alive))
+    }
+}
+
+/// Whether the idle connection `conn` can't be used anymore, because the
+/// server closed it or sent something without being asked.
+fn is_closed(conn: &mut Connection) -> bool {
+    conn.fill_buf().now_or_never().is_some()
+}
+
+/// Counts a request as queued while it exists.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl<'a> Queued<'a> {
+    fn new(queued: &'a AtomicUsize) -> Queued<'a> {
+        queued.fetch_add(1, Ordering::Relaxed);
+        Queued(queued)
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        auth: Option<&str>,
+        body: &[u8],
+    ) -> crate::Result<(u16, Vec<u8>)> {
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n",
+            method,
+            path,
+            self.host,
+            body.len(),
+        );
+        if let Some(auth) = auth {
+            head.push_str("Authorization: ");
+            head.push_str(auth);
+            head.push_str("\r\n");
+        }
+        head.push_str("\r\n");
+        // Send the request in a single write, so it isn't delayed by the
+        // Nagle algorithm.
+        let mut request = head.into_bytes();
+        request.extend_from_slice(body);
+
+        // Semaphores are fair, so requests get a connection in order.
+        let queued = Queued::new(&self.queued);
+        let _permit = self.permits.acquire().await.expect("the semaphore is never closed");
+        drop(queued);
+
+        let idle = self.idle.lock().unwrap().pop();
+        let (mut conn, mut reused) = match idle {
+            Some(mut conn) => match is_closed(&mut conn) {
+                false => (conn, true),
+                true => (self.connect().await?, false),
+            },
+            None => (self.connect().await?, false),
+        };
+        loop {
+            if reused {
+                self.connections_reused.fetch_add(1, Ordering::Relaxed);
+            }
+            let mut written = false;
+            match self.exchange(&mut conn, &request, &mut written).await {
+                Ok((response_code, resp_body, keep_alive)) => {
+                    if keep_alive {
+                        self.idle.lock().unwrap().push(conn);
+                    }
+                    return Ok((response_code, resp_body));
+                }
+                // Writing to the reused connection failed, so the server
+                // closed it before it got the whole request and it is safe
+                // to send it again. Once the request was written, it may
+                // have been executed and is never sent again.
+                Err(ref e) if reused && !written => {
+                    debug!(target: "bitcoincore_rpc", "reconnecting after stale connection failed: {}", e);
+                    conn = self.connect().await?;
+                    reused = false;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            in_flight: self.max_connections - self.permits.available_permits(),
+            queued: self.queued.load(Ordering::Relaxed),
+            idle: self.idle.lock().unwrap().len(),
+            connections_opened: self.connections_opened.load(Ordering::Relaxed),
+            connections_reused: self.connections_reused.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Read a body sent with `Transfer-Encoding: chunked`, of at most `max`
+/// bytes.
+async fn read_chunked<R: AsyncBufRead + Unpin>(reader: &mut R, max: usize) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).await?;
+        let size = line.trim_end().split(';').next().unwrap_or("");
+        let size = usize::from_str_radix(size, 16).map_err(|_| invalid_response())?;
+        if size == 0 {
+            // Skip the trailers up to the empty line that ends the body, so
+            // the connection can be reused.
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await? == 0 || line.trim_end().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        let start = body.len();
+        if size > max - start {
+            return Err(response_too_large(max));
+        }
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).await?;
+        // Skip the CRLF after the chunk data.
+        line.clear();
+        reader.read_line(&mut line).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Auth, Client, RpcApi};
+    use crate::test_server::read_request;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    /// Answer requests on keep-alive connections after a delay, counting the
+    /// connections and the most requests handled at the same time.
+    async fn serve_keep_alive(
+        listener: TcpListener,
+        busy: Arc<AtomicUsize>,
+        max: Arc<AtomicUsize>,
+    ) {
+        loop {
+            let (sock, _) = listener.accept().await.unwrap();
+            let (busy, max) = (busy.clone(), max.clone());
+            tokio::spawn(async move {
+                let mut reader = BufReader::new(sock);
+                let mut line = String::new();
+                loop {
+                    let mut content_length = 0;
+                    loop {
+                        line.clear();
+                        if reader.read_line(&mut line).await.unwrap() == 0 {
+                            return;
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some(len) = line.strip_prefix("Content-Length: ") {
+                            content_length = len.trim().parse().unwrap();
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).await.unwrap();
+
+                    let now = busy.fetch_add(1, Ordering::SeqCst) + 1;
+                    max.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                    busy.fetch_sub(1, Ordering::SeqCst);
+                    let body = r#"{"result":42,"error":null,"id":1}"#;
+                    let resp = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    reader.get_mut().write_all(resp.as_bytes()).await.unwrap();
+                }
+            });
+        }
+    }
+
+    /// Answer the first request on every connection and then close it,
+    /// after reading the next request if `read_next`, counting the requests.
+    async fn serve_once(listener: TcpListener, read_next: bool, requests: Arc<AtomicUsize>) {
+        loop {
+            let (sock, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(sock);
+            let mut line = String::new();
+            let mut content_length = 0;
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).await.unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+                if let Some(len) = line.strip_prefix("Content-Length: ") {
+                    content_length = len.trim().parse().unwrap();
+                }
+            }
+            reader.read_exact(&mut vec![0; content_length]).await.unwrap();
+            let body = r#"{"result":42,"error":null,"id":1}"#;
+            let resp = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+            reader.get_mut().write_all(resp.as_bytes()).await.unwrap();
+            requests.fetch_add(1, Ordering::SeqCst);
+            if read_next {
+                let mut buf = [0; 1024];
+                if reader.read(&mut buf).await.unwrap() > 0 {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_connection() {
+        // The server closes idle connections, which is noticed before they
+        // are used again.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let server = tokio::spawn(serve_once(listener, false, requests.clone()));
+        let client = Client::new(url, Auth::None).await.unwrap();
+        for _ in 0..3 {
+            assert_eq!(client.get_block_count().await.unwrap(), 42);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(client.pool_stats().unwrap().connections_opened, 3);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        server.abort();
+
+        // A request that was sent is not sent again when the connection is
+        // closed before the response, since the node may have executed it.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let server = tokio::spawn(serve_once(listener, true, requests.clone()));
+        let client = Client::new(url, Auth::None).await.unwrap();
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+        assert!(client.call::<String>("sendtoaddress", &[]).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_pool() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let max = Arc::new(AtomicUsize::new(0));
+        let server = tokio::spawn(serve_keep_alive(listener, Arc::default(), max.clone()));
+
+        let client =
+            crate::ClientBuilder::new(url, Auth::None).max_connections(4).build().await.unwrap();
+        let calls = (0..20).map(|_| client.get_block_count());
+        let counts = futures::future::join_all(calls).await;
+        assert!(counts.into_iter().all(|count| count.unwrap() == 42));
+        assert_eq!(max.load(Ordering::SeqCst), 4);
+
+        let stats = client.pool_stats().unwrap();
+        assert_eq!((stats.in_flight, stats.queued, stats.idle), (0, 0, 4));
+        assert_eq!(stats.connections_opened, 4);
+        assert_eq!(stats.connections_reused, 16);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_chunked_keep_alive() {
+        // Connections that carried a chunked response, with or without
+        // trailers, are reused.
+        const BODY: &str = r#"{"result":42,"error":null,"id":1}"#;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            loop {
+                let (mut sock, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    for trailer in ["", "X-Trailer: 1\r\n"].iter().cycle() {
+                        if read_request(&mut sock).await.unwrap().path.is_empty() {
+                            return;
+                        }
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                             {:x}\r\n{}\r\n0\r\n{}\r\n",
+                            BODY.len(),
+                            BODY,
+                            trailer
+                        );
+                        sock.write_all(response.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        let client = Client::new(url, Auth::None).await.unwrap();
+        for _ in 0..3 {
+            assert_eq!(client.get_block_count().await.unwrap(), 42);
+        }
+        let stats = client.pool_stats().unwrap();
+        assert_eq!((stats.connections_opened, stats.connections_reused), (1, 2));
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_invalid_status_line() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            for status in ["HTTP/1.1 2\r\n", "HTTP/1.1 20\u{e9}\r\n"] {
+                let (mut sock, _) = listener.accept().await.unwrap();
+                read_request(&mut sock).await.unwrap();
+                sock.write_all(status.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = Client::new(url, Auth::None).await.unwrap();
+        for _ in 0..2 {
+            match client.get_block_count().await {
+                Err(crate::Error::JsonRpc(_)) => {}
+                r => panic!("expected an error, got {:?}", r),
+            }
+        }
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_max_response_size() {
+        const BODY: &str = r#"{"result":42,"error":null,"id":1}"#;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let responses = [
+                // Never sent, the client must give up before allocating.
+                "HTTP/1.1 200 OK\r\nContent-Length: 1099511627776\r\n\r\n".to_owned(),
+                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", BODY.len(), BODY),
+                format!("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n40\r\n{:64}", ""),
+                format!("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{:64}", ""),
+            ];
+            for response in responses {
+                let (mut sock, _) = listener.accept().await.unwrap();
+                read_request(&mut sock).await.unwrap();
+                sock.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = crate::ClientBuilder::new(url, Auth::None)
+            .max_response_size(BODY.len())
+            .build()
+            .await
+            .unwrap();
+        assert!(client.get_block_count().await.is_err());
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+        assert!(client.get_block_count().await.is_err());
+        assert!(client.get_block_count().await.is_err());
+        server.await.unwrap();
+    }
+}
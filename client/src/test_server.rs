@@ -0,0 +1,96 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! A minimal HTTP server for the unit tests.
+
+use std::io;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// An HTTP request read by [read_request].
+pub(crate) struct Request {
+    pub(crate) path: String,
+    /// The header lines, without the line endings.
+    pub(crate) headers: Vec<String>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of the header `name`.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some(value.trim()).filter(|_| key.eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// The body parsed as JSON.
+    pub(crate) fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+/// Read a request from `sock`, including the body of `Content-Length` bytes.
+pub(crate) async fn read_request<S: AsyncRead + Unpin>(sock: &mut S) -> io::Result<Request> {
+    let mut reader = BufReader::new(sock);
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let path = line.split(' ').nth(1).unwrap_or_default().to_owned();
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 || line == "\r\n" {
+            break;
+        }
+        headers.push(line.trim_end().to_owned());
+    }
+    let mut request = Request {
+        path,
+        headers,
+        body: Vec::new(),
+    };
+    let len = request.header("Content-Length").map_or(0, |len| len.parse().unwrap());
+    request.body.resize(len, 0);
+    reader.read_exact(&mut request.body).await?;
+    Ok(request)
+}
+
+/// Write a response with `status` and `body` to `sock` and close the
+/// connection.
+pub(crate) async fn write_response<S: AsyncWrite + Unpin>(
+    sock: &mut S,
+    status: u16,
+    body: &[u8],
+) -> io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} -\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+        status,
+        body.len()
+    );
+    sock.write_all(head.as_bytes()).await?;
+    sock.write_all(body).await?;
+    sock.shutdown().await
+}
+
+/// Serve `n` requests, one per connection, answering each with the status
+/// and body returned by `reply`.
+pub(crate) async fn serve<F, B>(listener: TcpListener, n: usize, mut reply: F)
+where
+    F: FnMut(Request) -> (u16, B),
+    B: AsRef<[u8]>,
+{
+    for _ in 0..n {
+        let (mut sock, _) = listener.accept().await.unwrap();
+        let request = read_request(&mut sock).await.unwrap();
+        let (status, body) = reply(request);
+        write_response(&mut sock, status, body.as_ref()).await.unwrap();
+    }
+}
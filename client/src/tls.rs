@@ -0,0 +1,368 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! `https://` URLs with rustls, with the `tls` feature.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, CryptoProvider};
+use tokio_rustls::rustls::{
+    self, CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use tokio_rustls::TlsConnector;
+
+use crate::bitcoin::hashes::{sha256, Hash};
+use crate::client::Result;
+use crate::error::Error;
+
+/// The TLS settings of a client, see [crate::ClientBuilder::tls].
+///
+/// By default, the server certificate has to be issued for the host of the
+/// URL by one of the root certificates of the Mozilla root program.
+///
+/// ```no_run
+/// # async fn example() -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::{Auth, ClientBuilder, TlsConfig};
+///
+/// let tls = TlsConfig::new()
+///     .no_default_roots()
+///     .add_root_certificates_pem(&std::fs::read("ca.pem")?)?
+///     .client_certificate_pem(&std::fs::read("client.pem")?, &std::fs::read("client.key")?)?;
+/// let client = ClientBuilder::new("https://node.example.com:8443".into(), Auth::None)
+///     .tls(tls)
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TlsConfig {
+    roots: Vec<CertificateDer<'static>>,
+    default_roots: bool,
+    pins: Vec<[u8; 32]>,
+    client_certificate: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+}
+
+impl TlsConfig {
+    /// Trust the root certificates of the Mozilla root program.
+    pub fn new() -> TlsConfig {
+        TlsConfig {
+            roots: Vec::new(),
+            default_roots: true,
+            pins: Vec::new(),
+            client_certificate: None,
+        }
+    }
+
+    /// Also trust the certificates in `pem`, like the CA certificate of a
+    /// private PKI.
+    pub fn add_root_certificates_pem(mut self, pem: &[u8]) -> Result<TlsConfig> {
+        let certs = CertificateDer::pem_slice_iter(pem)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Tls(format!("invalid root certificate: {}", e)))?;
+        if certs.is_empty() {
+            return Err(Error::Tls("no root certificate in PEM".into()));
+        }
+        self.roots.extend(certs);
+        Ok(self)
+    }
+
+    /// Also trust the DER encoded certificate `der`.
+    pub fn add_root_certificate_der(mut self, der: &[u8]) -> TlsConfig {
+        self.roots.push(CertificateDer::from(der.to_vec()));
+        self
+    }
+
+    /// Only trust the root certificates that were added, not the ones of the
+    /// Mozilla root program.
+    pub fn no_default_roots(mut self) -> TlsConfig {
+        self.default_roots = false;
+        self
+    }
+
+    /// Only accept a server certificate with the SHA-256 fingerprint
+    /// `sha256`, or one of the other pinned fingerprints.
+    ///
+    /// The fingerprint is taken over the DER encoding of the certificate,
+    /// like `openssl x509 -noout -fingerprint -sha256` shows it. With pins,
+    /// the roots, the host name and the validity period of the certificate
+    /// are not checked, so self-signed certificates can be used.
+    pub fn pin_certificate_sha256(mut self, sha256: [u8; 32]) -> TlsConfig {
+        self.pins.push(sha256);
+        self
+    }
+
+    /// Authenticate with the client certificate chain in `cert_pem` and its
+    /// private key in `key_pem`.
+    pub fn client_certificate_pem(mut self, cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsConfig> {
+        let chain = CertificateDer::pem_slice_iter(cert_pem)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Tls(format!("invalid client certificate: {}", e)))?;
+        if chain.is_empty() {
+            return Err(Error::Tls("no client certificate in PEM".into()));
+        }
+        let key = PrivateKeyDer::from_pem_slice(key_pem)
+            .map_err(|e| Error::Tls(format!("invalid client key: {}", e)))?;
+        self.client_certificate = Some((chain, key));
+        Ok(self)
+    }
+
+    /// Create the connector for these settings.
+    pub(crate) fn connector(&self) -> Result<TlsConnector> {
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| Error::Tls(e.to_string()))?;
+        let builder = if self.pins.is_empty() {
+            let mut roots = RootCertStore::empty();
+            if self.default_roots {
+                roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            }
+            for root in &self.roots {
+                roots.add(root.clone()).map_err(|e| Error::Tls(e.to_string()))?;
+            }
+            builder.with_root_certificates(roots)
+        } else {
+            builder.dangerous().with_custom_certificate_verifier(Arc::new(PinVerifier {
+                pins: self.pins.clone(),
+                provider,
+            }))
+        };
+        let config = match self.client_certificate {
+            Some((ref chain, ref key)) => builder
+                .with_client_auth_cert(chain.clone(), key.clone_key())
+                .map_err(|e| Error::Tls(e.to_string()))?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(TlsConnector::from(Arc::new(config)))
+    }
+}
+
+impl Default for TlsConfig {
+    fn default() -> TlsConfig {
+        TlsConfig::new()
+    }
+}
+
+impl Clone for TlsConfig {
+    fn clone(&self) -> TlsConfig {
+        TlsConfig {
+            roots: self.roots.clone(),
+            default_roots: self.default_roots,
+            pins: self.pins.clone(),
+            client_certificate: self
+                .client_certificate
+                .as_ref()
+                .map(|(chain, key)| (chain.clone(), key.clone_key())),
+        }
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Leave out the private key.
+        f.debug_struct("TlsConfig")
+            .field("roots", &self.roots.len())
+            .field("default_roots", &self.default_roots)
+            .field("pins", &self.pins.len())
+            .field("client_certificate", &self.client_certificate.is_some())
+            .finish()
+    }
+}
+
+/// Accepts the server certificates with a pinned fingerprint.
+#[derive(Debug)]
+struct PinVerifier {
+    pins: Vec<[u8; 32]>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = sha256::Hash::hash(end_entity).into_inner();
+        match self.pins.contains(&fingerprint) {
+            true => Ok(ServerCertVerified::assertion()),
+            false => Err(CertificateError::ApplicationVerificationFailure.into()),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Opens TLS sessions to the server of a [crate::TcpTransport].
+#[derive(Clone)]
+pub(crate) struct TlsConnect {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+impl TlsConnect {
+    /// Connect with `connector` to the server `hostname`, a DNS name or an
+    /// IP address.
+    pub(crate) fn new(connector: TlsConnector, hostname: &str) -> Option<TlsConnect> {
+        Some(TlsConnect {
+            connector,
+            server_name: ServerName::try_from(hostname.to_owned()).ok()?,
+        })
+    }
+
+    /// Start a TLS session on `sock`.
+    pub(crate) async fn connect(&self, sock: TcpStream) -> io::Result<TlsStream<TcpStream>> {
+        self.connector.connect(self.server_name.clone(), sock).await
+    }
+}
+
+impl fmt::Debug for TlsConnect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsConnect").field("server_name", &self.server_name).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Auth, RpcApi};
+    use crate::test_server::{read_request, write_response};
+    use crate::ClientBuilder;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use rustls::server::WebPkiClientVerifier;
+    use rustls::ServerConfig;
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    /// Serve JSON-RPC calls over TLS with `config` until aborted.
+    async fn serve(listener: TcpListener, config: ServerConfig) {
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        loop {
+            let (sock, _) = listener.accept().await.unwrap();
+            let mut sock = match acceptor.accept(sock).await {
+                Ok(sock) => sock,
+                Err(_) => continue,
+            };
+            if read_request(&mut sock).await.is_ok() {
+                let body = r#"{"result":42,"error":null,"id":0}"#;
+                let _ = write_response(&mut sock, 200, body.as_bytes()).await;
+            }
+        }
+    }
+
+    async fn get_block_count(url: &str, tls: TlsConfig) -> Result<u64> {
+        let client = ClientBuilder::new(url.to_owned(), Auth::None).tls(tls).build().await?;
+        client.get_block_count().await
+    }
+
+    #[tokio::test]
+    async fn test_tls() {
+        let provider = Arc::new(crypto::ring::default_provider());
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let server_key = KeyPair::generate().unwrap();
+        let server = CertificateParams::new(vec!["localhost".into()])
+            .unwrap()
+            .signed_by(&server_key, &ca, &ca_key)
+            .unwrap();
+        let client_key = KeyPair::generate().unwrap();
+        let client = CertificateParams::new(vec!["client".into()])
+            .unwrap()
+            .signed_by(&client_key, &ca, &ca_key)
+            .unwrap();
+
+        // A server that only accepts clients with a certificate of the CA.
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.der().clone()).unwrap();
+        let verifier = WebPkiClientVerifier::builder_with_provider(roots.into(), provider.clone())
+            .build()
+            .unwrap();
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(
+                vec![server.der().clone()],
+                PrivateKeyDer::try_from(server_key.serialize_der()).unwrap(),
+            )
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("https://localhost:{}", listener.local_addr().unwrap().port());
+        let server_task = tokio::spawn(serve(listener, config));
+
+        let (client_pem, client_key_pem) = (client.pem(), client_key.serialize_pem());
+        let with_client_cert = |tls: TlsConfig| {
+            tls.client_certificate_pem(client_pem.as_bytes(), client_key_pem.as_bytes()).unwrap()
+        };
+        let trusted = TlsConfig::new()
+            .no_default_roots()
+            .add_root_certificates_pem(ca.pem().as_bytes())
+            .unwrap();
+        assert_eq!(get_block_count(&url, with_client_cert(trusted.clone())).await.unwrap(), 42);
+        // The server requires a client certificate.
+        assert!(get_block_count(&url, trusted).await.is_err());
+        // The CA is not trusted by default.
+        assert!(get_block_count(&url, with_client_cert(TlsConfig::new())).await.is_err());
+
+        // A pinned certificate is accepted without a trusted root.
+        let fingerprint = sha256::Hash::hash(server.der()).into_inner();
+        let pinned = TlsConfig::new().pin_certificate_sha256(fingerprint);
+        assert_eq!(get_block_count(&url, with_client_cert(pinned)).await.unwrap(), 42);
+        let wrong_pin = TlsConfig::new().pin_certificate_sha256([0; 32]);
+        assert!(get_block_count(&url, with_client_cert(wrong_pin)).await.is_err());
+
+        assert!(TlsConfig::new().add_root_certificates_pem(b"not a certificate").is_err());
+        server_task.abort();
+    }
+}
@@ -0,0 +1,616 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! The [Transport] trait and the HTTP layer on top of it that can send
+//! requests to several endpoints (like `/wallet/<name>`) of the same server.
+
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::{fmt, io};
+
+use async_trait::async_trait;
+use jsonrpc::simple_http::Error;
+use jsonrpc_async as jsonrpc;
+use log::debug;
+use serde::Serialize;
+
+use crate::client::Auth;
+use crate::metrics::Metrics;
+#[cfg(all(feature = "minimal", not(feature = "tokio")))]
+use crate::minimal::MinimalTransport;
+#[cfg(feature = "tokio")]
+use crate::socks::{self, Socks5Proxy};
+#[cfg(feature = "tokio")]
+use crate::tcp::TcpTransport;
+#[cfg(feature = "tls")]
+use crate::tls::TlsConnect;
+
+/// The default port of the Bitcoin Core JSON-RPC server.
+pub(crate) const DEFAULT_PORT: u16 = 8332;
+
+/// Sends HTTP requests to a bitcoind server, see
+/// [crate::ClientBuilder::transport].
+///
+/// Implement this to reach the server in other ways than the built-in
+/// transports, like through an SSH tunnel or a Unix domain socket proxy,
+/// or to send the requests with another HTTP client. Authentication, retries
+/// of rejected cookies and JSON encoding are left to the client.
+///
+/// The built-in transport is chosen with cargo features:
+///
+/// * `tokio` (the default): [crate::TcpTransport], with a pool of keep-alive
+///   connections, SOCKS5 proxies and TLS.
+/// * `minimal`: [crate::MinimalTransport], which opens a connection per
+///   request and works with any executor, without tokio. It is used when the
+///   `tokio` feature is disabled.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// Send a request with the HTTP `method` (`"POST"` or `"GET"`) and
+    /// `body` to `path`, with the `Authorization` header `auth` if set, and
+    /// return the status code and body of the response.
+    ///
+    /// An HTTP error status is not an error of the transport, bitcoind also
+    /// replies with JSON-RPC errors that way. Failures to reach the server
+    /// are best returned as [crate::Error::Io].
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        auth: Option<&str>,
+        body: &[u8],
+    ) -> crate::Result<(u16, Vec<u8>)>;
+
+    /// Statistics of the connection pool, if the transport has one.
+    fn stats(&self) -> Option<PoolStats> {
+        None
+    }
+}
+
+impl fmt::Debug for dyn Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Transport")
+    }
+}
+
+/// The settings of an [HttpTransport] besides its URL.
+#[derive(Default)]
+pub(crate) struct HttpOptions {
+    pub user_pass: Option<(String, String)>,
+    /// The cookie file to re-read the credentials from when they are rejected.
+    pub cookie_file: Option<PathBuf>,
+    /// The metrics to report the bytes transferred to.
+    pub metrics: Option<Arc<dyn Metrics>>,
+    /// The transport to use instead of the built-in one.
+    pub transport: Option<Arc<dyn Transport>>,
+    /// The maximum number of connections of the [crate::TcpTransport].
+    pub max_connections: Option<usize>,
+    /// The largest response body the built-in transport accepts.
+    pub max_response_size: Option<usize>,
+    /// The SOCKS5 proxy to connect through.
+    #[cfg(feature = "tokio")]
+    pub proxy: Option<Socks5Proxy>,
+    /// The TLS settings for `https` URLs, the defaults of
+    /// [crate::TlsConfig] if not set.
+    #[cfg(feature = "tls")]
+    pub tls: Option<tokio_rustls::TlsConnector>,
+}
+
+/// An HTTP connection configuration to a single server.
+#[derive(Debug)]
+pub(crate) struct HttpTransport {
+    transport: Arc<dyn Transport>,
+    /// The scheme of the URL, `http` or `https`.
+    scheme: &'static str,
+    /// The host and port of the server.
+    host: String,
+    /// The value of the `Authorization` HTTP header.
+    basic_auth: RwLock<Option<String>>,
+    /// The cookie file to re-read the credentials from when they are rejected.
+    cookie_file: Option<PathBuf>,
+    /// The metrics to report the bytes transferred to.
+    metrics: Option<Arc<dyn Metrics>>,
+}
+
+impl HttpTransport {
+    /// Send `body` as a POST request to `path` and parse the JSON reply.
+    ///
+    /// When using cookie authentication and the server rejects the
+    /// credentials, which happens when bitcoind restarts and writes a new
+    /// cookie, the cookie file is read again and the request is retried once.
+    ///
+    /// Timeouts are left to the caller, see [crate::ClientBuilder::timeout].
+    async fn request<R>(&self, path: &str, body: &impl serde::Serialize) -> Result<R, Error>
+    where
+        R: for<'a> serde::de::Deserialize<'a>,
+    {
+        let body = serde_json::to_vec(body)?;
+        let auth = self.basic_auth.read().unwrap().clone();
+        match self.send(path, &body, auth.as_deref()).await {
+            Err(Error::HttpErrorCode(401)) if self.cookie_file.is_some() => {
+                let auth = self.refresh_cookie(auth.as_deref())?;
+                self.send(path, &body, auth.as_deref()).await
+            }
+            res => res,
+        }
+    }
+
+    /// The scheme of the URL, `http` or `https`.
+    pub(crate) fn scheme(&self) -> &str {
+        self.scheme
+    }
+
+    /// The host and port of the server.
+    pub(crate) fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Re-read the cookie file after the credentials `stale` were rejected.
+    ///
+    /// If a concurrent request already replaced `stale`, the new credentials
+    /// are returned without reading the file again.
+    fn refresh_cookie(&self, stale: Option<&str>) -> Result<Option<String>, Error> {
+        let cookie_file = self.cookie_file.as_ref().expect("cookie authentication");
+        let mut auth = self.basic_auth.write().unwrap();
+        if auth.as_deref() == stale {
+            match Auth::CookieFile(cookie_file.clone()).get_user_pass() {
+                Ok(user_pass) => *auth = user_pass.map(basic_auth),
+                Err(e) => {
+                    debug!(target: "bitcoincore_rpc", "failed to re-read cookie file {:?}: {}", cookie_file, e);
+                    return Err(Error::HttpErrorCode(401));
+                }
+            }
+            debug!(target: "bitcoincore_rpc", "re-read cookie file {:?}", cookie_file);
+        }
+        Ok(auth.clone())
+    }
+
+    /// Send a single POST request with the authorization header `auth`.
+    async fn send<R>(&self, path: &str, body: &[u8], auth: Option<&str>) -> Result<R, Error>
+    where
+        R: for<'a> serde::de::Deserialize<'a>,
+    {
+        let (response_code, resp_body) = self.exchange("POST", path, body, auth).await?;
+        if response_code == 401 {
+            return Err(Error::HttpErrorCode(response_code));
+        }
+
+        // Even if it's != 200, we parse the response as we may get a JSONRPC error instead
+        // of the less meaningful HTTP error code.
+        match serde_json::from_slice(&resp_body) {
+            Ok(s) => Ok(s),
+            Err(_) if response_code != 200 => Err(Error::HttpErrorCode(response_code)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Statistics of the connection pool of the transport, if it has one.
+    pub(crate) fn stats(&self) -> Option<PoolStats> {
+        self.transport.stats()
+    }
+
+    /// Send a GET request to `path` without authorization, returning the
+    /// status code and body of the response.
+    pub(crate) async fn get(&self, path: &str) -> Result<(u16, Vec<u8>), Error> {
+        self.exchange("GET", path, &[], None).await
+    }
+
+    /// Send a single request with the transport and report the bytes
+    /// transferred.
+    async fn exchange(
+        &self,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        auth: Option<&str>,
+    ) -> Result<(u16, Vec<u8>), Error> {
+        let (response_code, resp_body) =
+            self.transport.send(method, path, auth, body).await.map_err(transport_error)?;
+        if let Some(ref metrics) = self.metrics {
+            metrics.bytes_transferred(body.len(), resp_body.len());
+        }
+        Ok((response_code, resp_body))
+    }
+}
+
+/// The number of connections [crate::TcpTransport] keeps to the server by
+/// default, the default `-rpcworkqueue` of bitcoind.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 16;
+
+/// The largest response body the built-in transports accept by default,
+/// 256 MiB, enough for the verbose mempool of a busy node.
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 256 << 20;
+
+/// Statistics of the connection pool of a [Transport].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Requests that were sent and wait for their response.
+    pub in_flight: usize,
+    /// Requests that wait for a free connection.
+    pub queued: usize,
+    /// Open connections that are not in use.
+    pub idle: usize,
+    /// Connections opened since the transport was created.
+    pub connections_opened: u64,
+    /// Requests sent over a connection that was used before.
+    pub connections_reused: u64,
+}
+
+/// The error for a response with a body larger than `max` bytes.
+pub(crate) fn response_too_large(max: usize) -> io::Error {
+    let msg = format!("HTTP response larger than the maximum of {} bytes", max);
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The error for a response that isn't valid HTTP.
+pub(crate) fn invalid_response() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "invalid HTTP response")
+}
+
+/// Turn an error of a [Transport] into an error of the JSON-RPC transport.
+fn transport_error(e: crate::Error) -> Error {
+    match e {
+        crate::Error::Io(e) => Error::SocketError(e),
+        e => Error::SocketError(io::Error::other(e)),
+    }
+}
+
+/// A JSON-RPC request with named parameters, which [jsonrpc::Request] can't
+/// represent.
+#[derive(Debug, Serialize)]
+pub(crate) struct NamedRequest<'a> {
+    pub method: &'a str,
+    pub params: &'a serde_json::Map<String, serde_json::Value>,
+    pub id: serde_json::Value,
+    pub jsonrpc: Option<&'a str>,
+}
+
+/// A JSON-RPC transport for one endpoint on the server of an [HttpTransport].
+#[derive(Clone, Debug)]
+pub(crate) struct Endpoint {
+    http: Arc<HttpTransport>,
+    path: String,
+}
+
+impl Endpoint {
+    /// Parse `url` to create an endpoint for its path, resolving its host
+    /// unless `options` has a transport or a proxy.
+    ///
+    /// With the `tokio` feature, `.onion` hosts are reached through the
+    /// proxy of a local Tor daemon if `options` has no proxy.
+    ///
+    /// If the credentials were read from a cookie file, they are read again
+    /// when the server rejects them.
+    pub(crate) async fn new(url: &str, options: HttpOptions) -> Result<Endpoint, Error> {
+        // Do some very basic manual URL parsing because the uri/url crates
+        // all have unicode-normalization as a dependency and that's broken.
+
+        // The fallback port in case no port was provided.
+        // This changes when the http or https scheme was provided.
+        let mut fallback_port = DEFAULT_PORT;
+        let mut https = false;
+
+        // (1) Split scheme
+        let after_scheme = match url.split_once("://") {
+            None => url,
+            Some(("http", after)) => {
+                fallback_port = 80;
+                after
+            }
+            Some(("https", after)) => {
+                fallback_port = 443;
+                https = true;
+                after
+            }
+            Some(_) => return Err(invalid_url(url, "scheme should be http or https")),
+        };
+        // (2) split off path
+        let (before_path, path) = match after_scheme.find('/') {
+            Some(slash) => after_scheme.split_at(slash),
+            None => (after_scheme, "/"),
+        };
+        // (3) split off auth part
+        let host = before_path.rsplit('@').next().unwrap();
+        // so now we should have <hostname>:<port> or just <hostname>,
+        // where <hostname> may be a bracketed IPv6 address
+        let (hostname, port) = match host.strip_prefix('[').and_then(|h| h.split_once(']')) {
+            Some((ipv6, "")) => (ipv6, None),
+            Some((ipv6, rest)) => (
+                ipv6,
+                Some(rest.strip_prefix(':').ok_or_else(|| invalid_url(url, "invalid port"))?),
+            ),
+            None => match host.split_once(':') {
+                Some((hostname, port)) => (hostname, Some(port)),
+                None => (host, None),
+            },
+        };
+        let port: u16 = match port {
+            Some(port) => port.parse().map_err(|_| invalid_url(url, "invalid port"))?,
+            None => fallback_port,
+        };
+
+        let host = match hostname.contains(':') {
+            true => format!("[{}]:{}", hostname, port),
+            false => format!("{}:{}", hostname, port),
+        };
+        let transport = match options.transport {
+            Some(ref transport) => transport.clone(),
+            None => default_transport(url, hostname, port, &host, https, &options).await?,
+        };
+        Ok(Endpoint {
+            http: Arc::new(HttpTransport {
+                transport,
+                scheme: if https {
+                    "https"
+                } else {
+                    "http"
+                },
+                host,
+                basic_auth: RwLock::new(options.user_pass.map(basic_auth)),
+                cookie_file: options.cookie_file,
+                metrics: options.metrics,
+            }),
+            path: path.to_owned(),
+        })
+    }
+
+    /// The connection configuration of the server.
+    pub(crate) fn http(&self) -> Arc<HttpTransport> {
+        self.http.clone()
+    }
+
+    /// The URL path of the endpoint.
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The endpoint of the wallet `name` on the same server.
+    pub(crate) fn wallet(&self, name: &str) -> Endpoint {
+        Endpoint {
+            http: self.http.clone(),
+            path: format!("{}/wallet/{}", self.path.trim_end_matches('/'), url_encode(name)),
+        }
+    }
+
+    /// Send a request with named parameters.
+    pub(crate) async fn send_named(
+        &self,
+        req: &NamedRequest<'_>,
+    ) -> Result<jsonrpc::Response, jsonrpc::Error> {
+        Ok(self.http.request(&self.path, req).await?)
+    }
+}
+
+#[async_trait]
+impl jsonrpc::Transport for Endpoint {
+    async fn send_request(
+        &self,
+        req: jsonrpc::Request<'_>,
+    ) -> Result<jsonrpc::Response, jsonrpc::Error> {
+        Ok(self.http.request(&self.path, &req).await?)
+    }
+
+    async fn send_batch(
+        &self,
+        reqs: &[jsonrpc::Request<'_>],
+    ) -> Result<Vec<jsonrpc::Response>, jsonrpc::Error> {
+        Ok(self.http.request(&self.path, &reqs).await?)
+    }
+
+    fn fmt_target(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}{}", self.http.scheme, self.http.host, self.path)
+    }
+}
+
+/// The value of the `Authorization` header for basic authentication.
+fn basic_auth((user, pass): (String, String)) -> String {
+    format!("Basic {}", base64::encode(format!("{}:{}", user, pass).as_bytes()))
+}
+
+/// Create a [TcpTransport] to `hostname` and `port` of `url`, with `host` as
+/// the `Host` HTTP header.
+#[cfg(feature = "tokio")]
+async fn default_transport(
+    url: &str,
+    hostname: &str,
+    port: u16,
+    host: &str,
+    https: bool,
+    options: &HttpOptions,
+) -> Result<Arc<dyn Transport>, Error> {
+    let proxy = match options.proxy {
+        Some(ref proxy) => Some(proxy.clone()),
+        None if socks::is_onion(hostname) => Some(Socks5Proxy::tor()),
+        None => None,
+    };
+    let mut transport = match proxy {
+        Some(proxy) => {
+            TcpTransport::new(proxy.addr(), host.to_owned()).proxy(proxy, hostname, port)
+        }
+        None => match tokio::net::lookup_host((hostname, port)).await?.next() {
+            Some(addr) => TcpTransport::new(addr, host.to_owned()),
+            None => {
+                return Err(invalid_url(url, "invalid hostname: error extracting socket address"))
+            }
+        },
+    };
+    if https {
+        transport = tls_transport(transport, url, hostname, options)?;
+    }
+    if let Some(max) = options.max_connections {
+        transport = transport.max_connections(max);
+    }
+    if let Some(max) = options.max_response_size {
+        transport = transport.max_response_size(max);
+    }
+    Ok(Arc::new(transport))
+}
+
+/// Create a [MinimalTransport] to `hostname` and `port` of `url`, with
+/// `host` as the `Host` HTTP header.
+#[cfg(all(feature = "minimal", not(feature = "tokio")))]
+async fn default_transport(
+    url: &str,
+    hostname: &str,
+    port: u16,
+    host: &str,
+    https: bool,
+    options: &HttpOptions,
+) -> Result<Arc<dyn Transport>, Error> {
+    if https {
+        return Err(invalid_url(url, "https needs the tls feature"));
+    }
+    let mut transport = match async_net::resolve((hostname, port)).await?.first() {
+        Some(&addr) => MinimalTransport::new(addr, host.to_owned()),
+        None => return Err(invalid_url(url, "invalid hostname: error extracting socket address")),
+    };
+    if let Some(max) = options.max_response_size {
+        transport = transport.max_response_size(max);
+    }
+    Ok(Arc::new(transport))
+}
+
+/// Without a runtime feature there is no built-in transport, and the crate
+/// fails to build with the error in [crate::rt] alone.
+#[cfg(not(any(feature = "tokio", feature = "minimal")))]
+async fn default_transport(
+    _: &str,
+    _: &str,
+    _: u16,
+    _: &str,
+    _: bool,
+    _: &HttpOptions,
+) -> Result<Arc<dyn Transport>, Error> {
+    unreachable!()
+}
+
+/// Use TLS with the settings of `options` on the connections of `transport`
+/// to `hostname`.
+#[cfg(feature = "tls")]
+fn tls_transport(
+    transport: TcpTransport,
+    url: &str,
+    hostname: &str,
+    options: &HttpOptions,
+) -> Result<TcpTransport, Error> {
+    let connector = match options.tls {
+        Some(ref connector) => connector.clone(),
+        None => crate::TlsConfig::new().connector().expect("the default TLS settings are valid"),
+    };
+    match TlsConnect::new(connector, hostname) {
+        Some(tls) => Ok(transport.tls(tls)),
+        None => Err(invalid_url(url, "invalid hostname for TLS")),
+    }
+}
+
+#[cfg(all(feature = "tokio", not(feature = "tls")))]
+fn tls_transport(
+    _: TcpTransport,
+    url: &str,
+    _: &str,
+    _: &HttpOptions,
+) -> Result<TcpTransport, Error> {
+    Err(invalid_url(url, "https needs the tls feature"))
+}
+
+fn invalid_url(url: &str, reason: &'static str) -> Error {
+    Error::InvalidUrl {
+        url: url.to_owned(),
+        reason,
+    }
+}
+
+/// Percent-encode everything but unreserved characters, like `bitcoin-cli`
+/// does for wallet names.
+fn url_encode(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                ret.push(b as char)
+            }
+            _ => ret.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Client, RpcApi};
+    use crate::test_server::serve;
+    use std::fs;
+    use tokio::net::TcpListener;
+
+    /// Serve `n` requests, rejecting all but the credentials `user:pass`.
+    async fn serve_auth(listener: TcpListener, n: usize, user: &str, pass: &str) {
+        let expected = basic_auth((user.into(), pass.into()));
+        serve(listener, n, |req| {
+            if req.header("Authorization") == Some(expected.as_str()) {
+                (200, r#"{"result":42,"error":null,"id":1}"#)
+            } else {
+                (401, "")
+            }
+        })
+        .await
+    }
+
+    /// Answers every request with its method, path and body.
+    struct Echo;
+
+    #[async_trait]
+    impl Transport for Echo {
+        async fn send(
+            &self,
+            method: &str,
+            path: &str,
+            _: Option<&str>,
+            body: &[u8],
+        ) -> crate::Result<(u16, Vec<u8>)> {
+            let body: serde_json::Value = serde_json::from_slice(body)?;
+            let result = format!("{} {} {}", method, path, body["method"]);
+            let resp = serde_json::json!({"result": result, "error": null, "id": body["id"]});
+            Ok((200, serde_json::to_vec(&resp)?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_transport() {
+        // The host is not resolved with a custom transport.
+        let client = crate::ClientBuilder::new("http://node.invalid/".into(), Auth::None)
+            .transport(Arc::new(Echo))
+            .build()
+            .await
+            .unwrap();
+        let result: String = client.call("uptime", &[]).await.unwrap();
+        assert_eq!(result, "POST / \"uptime\"");
+        let result: String = client.wallet("w").call("getbalance", &[]).await.unwrap();
+        assert_eq!(result, "POST /wallet/w \"getbalance\"");
+    }
+
+    #[tokio::test]
+    async fn test_cookie_refresh() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let cookie = std::env::temp_dir().join(format!("bitcoincore-rpc-cookie-{}", addr.port()));
+        fs::write(&cookie, "__cookie__:old").unwrap();
+        let client = Client::new(format!("http://{}", addr), Auth::CookieFile(cookie.clone()))
+            .await
+            .unwrap();
+
+        // bitcoind restarted with a new cookie.
+        fs::write(&cookie, "__cookie__:new").unwrap();
+        let server = tokio::spawn(serve_auth(listener, 3, "__cookie__", "new"));
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+        assert_eq!(client.get_block_count().await.unwrap(), 42);
+        server.await.unwrap();
+        fs::remove_file(cookie).unwrap();
+    }
+}
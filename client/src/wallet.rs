@@ -0,0 +1,245 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Wallet-scoped client handles for nodes with multiple wallets loaded.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use jsonrpc_async as jsonrpc;
+use serde_json::value::RawValue;
+
+use crate::client::{Client, Result, RpcApi};
+use crate::error::Error;
+use crate::transport::Endpoint;
+
+/// Wallet methods, which act on the wallet of the endpoint they are sent to.
+///
+/// Wallet management methods that don't need a loaded wallet, like
+/// `createwallet` or `listwallets`, are node-level methods.
+///
+/// Sorted, so it can be searched with [slice::binary_search].
+const WALLET_METHODS: &[&str] = &[
+    "abandontransaction",
+    "abortrescan",
+    "addmultisigaddress",
+    "backupwallet",
+    "bumpfee",
+    "dumpprivkey",
+    "dumpwallet",
+    "encryptwallet",
+    "fundrawtransaction",
+    "generate",
+    "getaddressesbylabel",
+    "getaddressinfo",
+    "getbalance",
+    "getbalances",
+    "gethdkeys",
+    "getnewaddress",
+    "getrawchangeaddress",
+    "getreceivedbyaddress",
+    "getreceivedbylabel",
+    "gettransaction",
+    "getunconfirmedbalance",
+    "getwalletinfo",
+    "importaddress",
+    "importdescriptors",
+    "importmulti",
+    "importprivkey",
+    "importprunedfunds",
+    "importpubkey",
+    "importwallet",
+    "keypoolrefill",
+    "listaddressgroupings",
+    "listdescriptors",
+    "listlabels",
+    "listlockunspent",
+    "listreceivedbyaddress",
+    "listreceivedbylabel",
+    "listsinceblock",
+    "listtransactions",
+    "listunspent",
+    "lockunspent",
+    "migratewallet",
+    "newkeypool",
+    "psbtbumpfee",
+    "removeprunedfunds",
+    "rescanblockchain",
+    "send",
+    "sendall",
+    "sendmany",
+    "sendtoaddress",
+    "sethdseed",
+    "setlabel",
+    "settxfee",
+    "setwalletflag",
+    "signmessage",
+    "signrawtransactionwithwallet",
+    "simulaterawtransaction",
+    "unloadwallet",
+    "upgradewallet",
+    "walletcreatefundedpsbt",
+    "walletdisplayaddress",
+    "walletlock",
+    "walletpassphrase",
+    "walletpassphrasechange",
+    "walletprocesspsbt",
+];
+
+/// Whether `cmd` acts on the wallet of its endpoint.
+fn is_wallet_method(cmd: &str) -> bool {
+    WALLET_METHODS.binary_search(&cmd).is_ok()
+}
+
+/// A handle to a single wallet of a multi-wallet node.
+///
+/// Created with [Client::wallet]. Wallet RPCs are sent to the
+/// `/wallet/<name>` endpoint of the node and node-level RPCs to the endpoint
+/// of the [Client]. The handle shares the transport and authentication of
+/// its [Client].
+pub struct WalletClient<'a> {
+    client: &'a Client,
+    name: String,
+    /// The client and endpoint of the wallet, [None] if the [Client] was not
+    /// created from a URL.
+    wallet: Option<(jsonrpc::client::Client, Endpoint)>,
+}
+
+impl<'a> WalletClient<'a> {
+    pub(crate) fn new(
+        client: &'a Client,
+        name: &str,
+        wallet: Option<(jsonrpc::client::Client, Endpoint)>,
+    ) -> WalletClient<'a> {
+        WalletClient {
+            client,
+            name: name.to_owned(),
+            wallet,
+        }
+    }
+
+    /// The name of the wallet.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The client this handle was created from.
+    pub fn node(&self) -> &Client {
+        self.client
+    }
+}
+
+impl<'a> fmt::Debug for WalletClient<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bitcoincore_rpc::WalletClient({:?})", self.name)
+    }
+}
+
+#[async_trait]
+impl<'a> RpcApi for WalletClient<'a> {
+    /// Call a `cmd` rpc with given `args` list, on the wallet endpoint if
+    /// `cmd` is a wallet method.
+    async fn call<T: for<'b> serde::de::Deserialize<'b>>(
+        &self,
+        cmd: &str,
+        args: &[serde_json::Value],
+    ) -> Result<T> {
+        if !is_wallet_method(cmd) {
+            return self.client.call(cmd, args).await;
+        }
+        match self.wallet {
+            Some((ref wallet, _)) => self.client.call_on(wallet, Some(&self.name), cmd, args).await,
+            None => Err(Error::WalletEndpointUnavailable),
+        }
+    }
+
+    async fn call_raw(&self, cmd: &str, args: &[serde_json::Value]) -> Result<Box<RawValue>> {
+        if !is_wallet_method(cmd) {
+            return self.client.call_raw(cmd, args).await;
+        }
+        match self.wallet {
+            Some((ref wallet, _)) => {
+                self.client.call_raw_on(wallet, Some(&self.name), cmd, args).await
+            }
+            None => Err(Error::WalletEndpointUnavailable),
+        }
+    }
+
+    /// Call a `cmd` rpc with given named `args`, on the wallet endpoint if
+    /// `cmd` is a wallet method.
+    async fn call_named<T: for<'b> serde::de::Deserialize<'b>>(
+        &self,
+        cmd: &str,
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<T> {
+        if !is_wallet_method(cmd) {
+            return self.client.call_named(cmd, args).await;
+        }
+        match self.wallet {
+            Some((_, ref endpoint)) => {
+                self.client.call_named_on(endpoint, Some(&self.name), cmd, args).await
+            }
+            None => Err(Error::WalletEndpointUnavailable),
+        }
+    }
+
+    fn named_params(&self) -> bool {
+        self.client.named_params()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Auth;
+    use crate::test_server::serve;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_wallet_methods_sorted() {
+        assert!(WALLET_METHODS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[tokio::test]
+    async fn test_wallet_routing() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        // Reply with the request path as result.
+        let server = tokio::spawn(serve(listener, 6, |req| {
+            (200, format!(r#"{{"result":"{}","error":null,"id":1}}"#, req.path))
+        }));
+
+        let client = Client::new(url.clone(), Auth::None).await.unwrap();
+        let wallet = client.wallet("my wallet");
+        let path: String = wallet.call("getwalletinfo", &[]).await.unwrap();
+        assert_eq!(path, "/wallet/my%20wallet");
+        let raw = wallet.call_raw("listunspent", &[]).await.unwrap();
+        assert_eq!(raw.get(), r#""/wallet/my%20wallet""#);
+        // Node-level RPCs keep using the endpoint of the client.
+        let path: String = wallet.call("getblockcount", &[]).await.unwrap();
+        assert_eq!(path, "/");
+        let path: String = wallet.call_named("getblockhash", &Default::default()).await.unwrap();
+        assert_eq!(path, "/");
+        let path: String = client.call("getblockcount", &[]).await.unwrap();
+        assert_eq!(path, "/");
+
+        // Without a URL only the wallet RPCs fail.
+        let client =
+            Client::from_jsonrpc(jsonrpc::Client::simple_http(&url, None, None).await.unwrap());
+        let wallet = client.wallet("my wallet");
+        let path: String = wallet.call("getblockcount", &[]).await.unwrap();
+        assert_eq!(path, "/");
+        match wallet.call::<String>("getwalletinfo", &[]).await {
+            Err(Error::WalletEndpointUnavailable) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        server.await.unwrap();
+    }
+}
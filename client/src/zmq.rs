@@ -0,0 +1,464 @@
+// To the extent possible under law, the author(s) have dedicated all
+// copyright and related and neighboring rights to this software to
+// the public domain worldwide. This software is distributed without
+// any warranty.
+//
+// You should have received a copy of the CC0 Public Domain Dedication
+// along with this software.
+// If not, see <http://creativecommons.org/publicdomain/zero/1.0/>.
+//
+
+//! Subscribing to the ZMQ notifications of bitcoind.
+//!
+//! This speaks just enough of the ZeroMQ message transport protocol (ZMTP
+//! 3.0 without security) to subscribe to a publisher.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bitcoin::consensus::encode;
+use bitcoin::hashes::Hash;
+use bitcoin::{Block, BlockHash, Transaction, Txid};
+use futures::stream::{self, BoxStream, SelectAll, Stream, StreamExt};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::bitcoin;
+use crate::client::{Result, RpcApi};
+use crate::error::Error;
+use crate::rt::{AsyncReadExt, AsyncWriteExt, TcpStream};
+
+/// The largest frame that is accepted, larger than any block.
+const MAX_FRAME_SIZE: u64 = 32_000_000;
+
+/// The flag of frames that are followed by another frame of the same message.
+const FLAG_MORE: u8 = 0x01;
+/// The flag of frames with an 8 byte size.
+const FLAG_LONG: u8 = 0x02;
+/// The flag of command frames.
+const FLAG_COMMAND: u8 = 0x04;
+
+/// A notification enabled with `-zmqpub<type>=<address>`, as returned by
+/// [RpcApi::get_zmq_notifications].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GetZmqNotificationsResult {
+    /// The type of the notification, like `"pubhashblock"`.
+    #[serde(rename = "type")]
+    pub notification_type: String,
+    pub address: String,
+    /// The outbound message high water mark.
+    pub hwm: u64,
+}
+
+/// A topic of the ZMQ notifications of bitcoind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ZmqTopic {
+    HashBlock,
+    RawBlock,
+    HashTx,
+    RawTx,
+    Sequence,
+}
+
+impl ZmqTopic {
+    const ALL: [ZmqTopic; 5] = [
+        ZmqTopic::HashBlock,
+        ZmqTopic::RawBlock,
+        ZmqTopic::HashTx,
+        ZmqTopic::RawTx,
+        ZmqTopic::Sequence,
+    ];
+
+    /// The name of the topic in messages, like `"hashblock"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ZmqTopic::HashBlock => "hashblock",
+            ZmqTopic::RawBlock => "rawblock",
+            ZmqTopic::HashTx => "hashtx",
+            ZmqTopic::RawTx => "rawtx",
+            ZmqTopic::Sequence => "sequence",
+        }
+    }
+
+    /// The topic of a notification type returned by
+    /// [RpcApi::get_zmq_notifications], like `"pubhashblock"`.
+    pub fn from_notification_type(notification_type: &str) -> Option<ZmqTopic> {
+        let name = notification_type.strip_prefix("pub")?;
+        ZmqTopic::ALL.iter().copied().find(|t| t.as_str() == name)
+    }
+
+    fn from_bytes(name: &[u8]) -> Option<ZmqTopic> {
+        ZmqTopic::ALL.iter().copied().find(|t| t.as_str().as_bytes() == name)
+    }
+}
+
+/// A change of the best chain or the mempool reported on the `sequence`
+/// topic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SequenceEvent {
+    BlockConnected(BlockHash),
+    BlockDisconnected(BlockHash),
+    /// A transaction was added to the mempool, with the mempool sequence
+    /// number of the change.
+    TxAdded(Txid, u64),
+    /// A transaction was removed from the mempool for another reason than
+    /// being included in a block, with the mempool sequence number of the
+    /// change.
+    TxRemoved(Txid, u64),
+}
+
+/// The decoded payload of a ZMQ notification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ZmqMessage {
+    HashBlock(BlockHash),
+    RawBlock(Block),
+    HashTx(Txid),
+    RawTx(Transaction),
+    Sequence(SequenceEvent),
+}
+
+/// A notification received by a [ZmqSubscriber].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZmqNotification {
+    pub topic: ZmqTopic,
+    pub message: ZmqMessage,
+    /// The sequence number of the notification, counted per topic.
+    pub sequence: u32,
+    /// The number of notifications of the same topic that were dropped
+    /// between the previous one and this one, for example because the high
+    /// water mark of bitcoind was reached.
+    pub missed: u32,
+}
+
+/// Subscribes to the ZMQ notifications of bitcoind.
+///
+/// Notifications of all subscribed topics and addresses are received in the
+/// order they arrive. Dropped notifications are detected from the sequence
+/// numbers of their topic and reported in [ZmqNotification::missed], so the
+/// state can be synced with RPC calls instead.
+///
+/// ```no_run
+/// # async fn example(client: &bitcoincore_rpc_async::Client) -> bitcoincore_rpc_async::Result<()> {
+/// use bitcoincore_rpc_async::{ZmqMessage, ZmqSubscriber};
+///
+/// let mut subscriber = ZmqSubscriber::from_notifications(client).await?;
+/// loop {
+///     let notification = subscriber.next_notification().await?;
+///     if let ZmqMessage::RawBlock(block) = notification.message {
+///         println!("new block {}", block.block_hash());
+///     }
+/// }
+/// # }
+/// ```
+pub struct ZmqSubscriber {
+    messages: SelectAll<BoxStream<'static, Result<Vec<Vec<u8>>>>>,
+    sequences: HashMap<ZmqTopic, u32>,
+}
+
+impl ZmqSubscriber {
+    /// Subscribe to `topics` at the ZMQ address `address`, like
+    /// `tcp://127.0.0.1:28332`.
+    pub async fn connect(address: &str, topics: &[ZmqTopic]) -> Result<ZmqSubscriber> {
+        let mut subscriber = ZmqSubscriber {
+            messages: SelectAll::new(),
+            sequences: HashMap::new(),
+        };
+        subscriber.subscribe(address, topics).await?;
+        Ok(subscriber)
+    }
+
+    /// Subscribe to all notifications of the node of `client`, at the
+    /// addresses returned by [RpcApi::get_zmq_notifications].
+    ///
+    /// Fails if no notifications are enabled.
+    pub async fn from_notifications<C: RpcApi + Sync>(client: &C) -> Result<ZmqSubscriber> {
+        let mut addresses = BTreeMap::<String, Vec<ZmqTopic>>::new();
+        for notification in client.get_zmq_notifications().await? {
+            match ZmqTopic::from_notification_type(&notification.notification_type) {
+                Some(topic) => addresses.entry(notification.address).or_default().push(topic),
+                None => {
+                    debug!(target: "bitcoincore_rpc", "ignoring ZMQ notification type {}", notification.notification_type);
+                }
+            }
+        }
+        let mut addresses = addresses.into_iter();
+        let (address, topics) = addresses
+            .next()
+            .ok_or_else(|| Error::Zmq("no ZMQ notifications are enabled".to_owned()))?;
+        let mut subscriber = ZmqSubscriber::connect(&address, &topics).await?;
+        for (address, topics) in addresses {
+            subscriber.subscribe(&address, &topics).await?;
+        }
+        Ok(subscriber)
+    }
+
+    /// Also subscribe to `topics` at the ZMQ address `address`.
+    pub async fn subscribe(&mut self, address: &str, topics: &[ZmqTopic]) -> Result<()> {
+        let host = address.strip_prefix("tcp://").ok_or_else(|| {
+            Error::Zmq(format!("unsupported address {}, only tcp:// is supported", address))
+        })?;
+        let mut sock = TcpStream::connect(host).await?;
+        handshake(&mut sock, "SUB").await?;
+        for topic in topics {
+            // A subscription is a message starting with 1.
+            let mut subscription = vec![1];
+            subscription.extend_from_slice(topic.as_str().as_bytes());
+            write_frame(&mut sock, 0, &subscription).await?;
+        }
+        debug!(target: "bitcoincore_rpc", "subscribed to ZMQ topics {:?} at {}", topics, address);
+
+        let messages = stream::unfold(Some(sock), |sock| async move {
+            let mut sock = sock?;
+            match read_message(&mut sock).await {
+                Ok(message) => Some((Ok(message), Some(sock))),
+                // The connection can't be used after an error.
+                Err(e) => Some((Err(e), None)),
+            }
+        });
+        self.messages.push(messages.boxed());
+        Ok(())
+    }
+
+    /// Wait for the next notification.
+    ///
+    /// Fails if a connection was closed or a notification can't be decoded.
+    /// Notifications on the other connections can still be received after
+    /// an error.
+    pub async fn next_notification(&mut self) -> Result<ZmqNotification> {
+        loop {
+            let frames = match self.messages.next().await {
+                Some(frames) => frames?,
+                None => return Err(Error::Zmq("all connections are closed".to_owned())),
+            };
+            let (topic, body, sequence) = match frames[..] {
+                [ref topic, ref body, ref sequence] if sequence.len() == 4 => {
+                    let sequence =
+                        u32::from_le_bytes([sequence[0], sequence[1], sequence[2], sequence[3]]);
+                    (topic, body, sequence)
+                }
+                _ => {
+                    return Err(Error::Zmq(format!(
+                        "unexpected message with {} frames",
+                        frames.len()
+                    )))
+                }
+            };
+            let topic = match ZmqTopic::from_bytes(topic) {
+                Some(topic) => topic,
+                None => continue,
+            };
+            let missed = match self.sequences.insert(topic, sequence) {
+                Some(previous) => sequence.wrapping_sub(previous).wrapping_sub(1),
+                None => 0,
+            };
+            if missed > 0 {
+                debug!(target: "bitcoincore_rpc", "missed {} ZMQ {} notifications", missed, topic.as_str());
+            }
+            return Ok(ZmqNotification {
+                topic,
+                message: decode_message(topic, body)?,
+                sequence,
+                missed,
+            });
+        }
+    }
+
+    /// Turn the subscriber into a [Stream] of its notifications.
+    ///
+    /// The stream ends when all connections are closed.
+    pub fn into_stream(self) -> impl Stream<Item = Result<ZmqNotification>> {
+        stream::unfold(self, |mut subscriber| async move {
+            match subscriber.next_notification().await {
+                Err(Error::Zmq(_)) if subscriber.messages.is_empty() => None,
+                notification => Some((notification, subscriber)),
+            }
+        })
+    }
+}
+
+/// Decode the body of a notification of `topic`.
+fn decode_message(topic: ZmqTopic, body: &[u8]) -> Result<ZmqMessage> {
+    Ok(match topic {
+        ZmqTopic::HashBlock => ZmqMessage::HashBlock(decode_hash(body)?),
+        ZmqTopic::RawBlock => ZmqMessage::RawBlock(encode::deserialize(body)?),
+        ZmqTopic::HashTx => ZmqMessage::HashTx(decode_hash(body)?),
+        ZmqTopic::RawTx => ZmqMessage::RawTx(encode::deserialize(body)?),
+        ZmqTopic::Sequence => {
+            if body.len() < 33 {
+                return Err(Error::Zmq("sequence notification is too short".to_owned()));
+            }
+            let (hash, rest) = body.split_at(32);
+            let mempool_sequence = || match rest[1..] {
+                [a, b, c, d, e, f, g, h] => Ok(u64::from_le_bytes([a, b, c, d, e, f, g, h])),
+                _ => Err(Error::Zmq("sequence notification without mempool sequence".to_owned())),
+            };
+            let event = match rest[0] {
+                b'C' => SequenceEvent::BlockConnected(decode_hash(hash)?),
+                b'D' => SequenceEvent::BlockDisconnected(decode_hash(hash)?),
+                b'A' => SequenceEvent::TxAdded(decode_hash(hash)?, mempool_sequence()?),
+                b'R' => SequenceEvent::TxRemoved(decode_hash(hash)?, mempool_sequence()?),
+                label => {
+                    return Err(Error::Zmq(format!("unknown sequence label {}", label as char)))
+                }
+            };
+            ZmqMessage::Sequence(event)
+        }
+    })
+}
+
+/// Decode a hash, which is sent in the reversed byte order it is displayed in.
+fn decode_hash<H: Hash>(bytes: &[u8]) -> Result<H> {
+    let mut bytes = bytes.to_vec();
+    bytes.reverse();
+    H::from_slice(&bytes).map_err(|_| Error::Zmq(format!("invalid hash of {} bytes", bytes.len())))
+}
+
+/// Exchange greetings and `READY` commands with the peer on `sock`, as a
+/// socket of type `socket_type`.
+async fn handshake(sock: &mut TcpStream, socket_type: &str) -> Result<()> {
+    // Version 3.0 with the NULL security mechanism, as client.
+    let mut greeting = [0; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    sock.write_all(&greeting).await?;
+
+    let mut peer = [0; 64];
+    sock.read_exact(&mut peer).await?;
+    if peer[0] != 0xff || peer[9] != 0x7f || peer[10] < 3 || &peer[12..17] != b"NULL\0" {
+        return Err(Error::Zmq("unsupported ZMTP greeting".to_owned()));
+    }
+
+    let mut ready = b"\x05READY\x0bSocket-Type".to_vec();
+    ready.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    ready.extend_from_slice(socket_type.as_bytes());
+    write_frame(sock, FLAG_COMMAND, &ready).await?;
+
+    match read_frame(sock).await? {
+        (flags, ref body) if flags & FLAG_COMMAND != 0 && body.starts_with(b"\x05READY") => Ok(()),
+        (flags, ref body) if flags & FLAG_COMMAND != 0 && body.starts_with(b"\x05ERROR") => {
+            let reason = String::from_utf8_lossy(body.get(7..).unwrap_or_default());
+            Err(Error::Zmq(format!("peer rejected the connection: {}", reason)))
+        }
+        _ => Err(Error::Zmq("expected READY command".to_owned())),
+    }
+}
+
+/// Write a frame with `flags` and `body`, setting the size flag as needed.
+async fn write_frame(sock: &mut TcpStream, flags: u8, body: &[u8]) -> Result<()> {
+    let mut frame = Vec::with_capacity(body.len() + 9);
+    if body.len() > u8::MAX as usize {
+        frame.push(flags | FLAG_LONG);
+        frame.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    } else {
+        frame.push(flags);
+        frame.push(body.len() as u8);
+    }
+    frame.extend_from_slice(body);
+    sock.write_all(&frame).await?;
+    Ok(())
+}
+
+/// Read a frame and return its flags and body.
+async fn read_frame(sock: &mut TcpStream) -> Result<(u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    sock.read_exact(&mut head).await?;
+    let flags = head[0];
+    let size = match flags & FLAG_LONG {
+        0 => head[1] as u64,
+        _ => {
+            let mut size = [0; 8];
+            size[0] = head[1];
+            sock.read_exact(&mut size[1..]).await?;
+            u64::from_be_bytes(size)
+        }
+    };
+    if size > MAX_FRAME_SIZE {
+        return Err(Error::Zmq(format!("frame of {} bytes is too large", size)));
+    }
+    let mut body = vec![0; size as usize];
+    sock.read_exact(&mut body).await?;
+    Ok((flags, body))
+}
+
+/// Read the frames of the next message, skipping commands.
+async fn read_message(sock: &mut TcpStream) -> Result<Vec<Vec<u8>>> {
+    let mut frames = Vec::new();
+    loop {
+        let (flags, body) = read_frame(sock).await?;
+        if flags & FLAG_COMMAND != 0 {
+            continue;
+        }
+        frames.push(body);
+        if flags & FLAG_MORE == 0 {
+            return Ok(frames);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::blockdata::constants::genesis_block;
+    use crate::bitcoin::consensus::encode::serialize;
+    use crate::bitcoin::Network;
+    #[cfg(all(feature = "minimal", not(feature = "tokio")))]
+    use async_net::TcpListener;
+    #[cfg(feature = "tokio")]
+    use tokio::net::TcpListener;
+
+    /// Accept a subscriber to `hashblock` and publish `messages`, each as
+    /// topic, body and sequence number.
+    async fn publish(listener: TcpListener, messages: Vec<(&'static str, Vec<u8>, u32)>) {
+        let (mut sock, _) = listener.accept().await.unwrap();
+        handshake(&mut sock, "PUB").await.unwrap();
+        assert_eq!(read_message(&mut sock).await.unwrap(), vec![b"\x01hashblock".to_vec()]);
+        for (topic, body, sequence) in messages {
+            write_frame(&mut sock, FLAG_MORE, topic.as_bytes()).await.unwrap();
+            write_frame(&mut sock, FLAG_MORE, &body).await.unwrap();
+            write_frame(&mut sock, 0, &sequence.to_le_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_zmq_subscriber() {
+        let block = genesis_block(Network::Regtest);
+        let hash = block.block_hash();
+        let mut reversed = hash.into_inner();
+        reversed.reverse();
+        let mut added = reversed.to_vec();
+        added.push(b'A');
+        added.extend_from_slice(&7u64.to_le_bytes());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("tcp://{}", listener.local_addr().unwrap());
+        let publisher = tokio::spawn(publish(
+            listener,
+            vec![
+                ("hashblock", reversed.to_vec(), 0),
+                ("rawblock", serialize(&block), 0),
+                ("sequence", added, 3),
+                ("hashblock", reversed.to_vec(), 2),
+            ],
+        ));
+
+        let mut subscriber =
+            ZmqSubscriber::connect(&address, &[ZmqTopic::HashBlock]).await.unwrap();
+        let notification = subscriber.next_notification().await.unwrap();
+        assert_eq!(notification.message, ZmqMessage::HashBlock(hash));
+        let notification = subscriber.next_notification().await.unwrap();
+        assert_eq!(notification.message, ZmqMessage::RawBlock(block));
+        let notification = subscriber.next_notification().await.unwrap();
+        let txid = Txid::from_inner(hash.into_inner());
+        assert_eq!(notification.message, ZmqMessage::Sequence(SequenceEvent::TxAdded(txid, 7)));
+        assert_eq!(notification.missed, 0);
+        let notification = subscriber.next_notification().await.unwrap();
+        assert_eq!((notification.topic, notification.sequence), (ZmqTopic::HashBlock, 2));
+        assert_eq!(notification.missed, 1);
+        publisher.await.unwrap();
+
+        // The connection was closed by the publisher.
+        assert!(subscriber.next_notification().await.is_err());
+        assert!(Box::pin(subscriber.into_stream()).next().await.is_none());
+    }
+}
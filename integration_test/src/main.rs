@@ -19,8 +19,7 @@ use tokio;
 use std::collections::HashMap;
 
 use bitcoincore_rpc::json;
-use bitcoincore_rpc::jsonrpc::error::Error as JsonRpcError;
-use bitcoincore_rpc::{Auth, Client, Error, RpcApi};
+use bitcoincore_rpc::{Auth, Client, RpcApi, RpcErrorCode};
 use bitcoincore_rpc_async as bitcoincore_rpc;
 
 use bitcoin::consensus::encode::{deserialize, serialize};
@@ -64,7 +63,7 @@ static LOGGER: StdLogger = StdLogger;
 macro_rules! assert_deprecated {
     ($call:expr) => {
         match $call.await.unwrap_err() {
-            Error::JsonRpc(JsonRpcError::Rpc(ref e)) if e.code == -32 => {}
+            ref e if e.rpc_code() == Some(RpcErrorCode::MethodDeprecated) => {}
             e => panic!("expected deprecated error for {}, got: {}", stringify!($call), e),
         }
     };
@@ -74,7 +73,7 @@ macro_rules! assert_deprecated {
 macro_rules! assert_not_found {
     ($call:expr) => {
         match $call.await.unwrap_err() {
-            Error::JsonRpc(JsonRpcError::Rpc(ref e)) if e.code == -32601 => {}
+            ref e if e.is_method_not_found() => {}
             e => panic!("expected method not found error for {}, got: {}", stringify!($call), e),
         }
     };
@@ -975,8 +974,7 @@ async fn test_create_wallet(cl: &Client) {
         };
         assert_eq!(result.warning, expected_warning);
 
-        let wallet_client_url = format!("{}{}{}", get_rpc_url(), "/wallet/", wallet_param.name);
-        let wallet_client = Client::new(wallet_client_url, get_auth()).await.unwrap();
+        let wallet_client = cl.wallet(wallet_param.name);
         let wallet_info = wallet_client.get_wallet_info().await.unwrap();
 
         assert_eq!(wallet_info.wallet_name, wallet_param.name);